@@ -0,0 +1,231 @@
+//! Derive macros for the `FromRow` and `ToParams` traits of `rusqlite`.
+//!
+//! Use them through the `derive` feature of `rusqlite`:
+//!
+//! ```rust,ignore
+//! use rusqlite::{FromRow, ToParams};
+//!
+//! #[derive(FromRow, ToParams)]
+//! struct Person {
+//!     id: i64,
+//!     #[rusqlite(rename = "full_name")]
+//!     name: String,
+//!     email: Option<String>,
+//!     #[rusqlite(skip)]
+//!     cached: Vec<u8>,
+//! }
+//! ```
+//!
+//! Fields accept the following attributes:
+//! * `#[rusqlite(rename = "name")]` reads the column, or binds the parameter,
+//!   `name` instead of the field name,
+//! * `#[rusqlite(index = N)]` reads the column at index `N` (`FromRow` only),
+//! * `#[rusqlite(skip)]` ignores the field, which is set to its `Default`
+//!   value by `FromRow`; in tuple structs, the next field takes its column
+//!   index or parameter position,
+//! * `#[rusqlite(default)]` uses the `Default` value of the field when the
+//!   row has no such column (`FromRow` only).
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, Attribute, Data, DeriveInput, Error, Field, Fields, Index, Lit, Meta,
+    NestedMeta,
+};
+
+/// Derive `rusqlite::FromRow`.
+///
+/// Fields of structs with named fields are read by column name, fields of
+/// tuple structs by column index, skipped fields excluded.
+#[proc_macro_derive(FromRow, attributes(rusqlite))]
+pub fn derive_from_row(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    from_row(&input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Derive `rusqlite::ToParams`.
+///
+/// Fields of structs with named fields are bound to the parameters named
+/// `:field`, `@field` or `$field` found in the statement, fields of tuple
+/// structs to the parameters at the same (one-based) position, skipped
+/// fields excluded.
+#[proc_macro_derive(ToParams, attributes(rusqlite))]
+pub fn derive_to_params(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    to_params(&input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+#[derive(Default)]
+struct FieldAttrs {
+    rename: Option<String>,
+    index: Option<usize>,
+    skip: bool,
+    default: bool,
+}
+
+impl FieldAttrs {
+    fn parse(attrs: &[Attribute]) -> syn::Result<FieldAttrs> {
+        let mut field_attrs = FieldAttrs::default();
+        for attr in attrs.iter().filter(|attr| attr.path.is_ident("rusqlite")) {
+            let list = match attr.parse_meta()? {
+                Meta::List(list) => list,
+                meta => return Err(Error::new_spanned(meta, "expected #[rusqlite(...)]")),
+            };
+            for nested in list.nested {
+                match nested {
+                    NestedMeta::Meta(Meta::Path(ref path)) if path.is_ident("skip") => {
+                        field_attrs.skip = true;
+                    }
+                    NestedMeta::Meta(Meta::Path(ref path)) if path.is_ident("default") => {
+                        field_attrs.default = true;
+                    }
+                    NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.path.is_ident("rename") => {
+                        match nv.lit {
+                            Lit::Str(ref s) => field_attrs.rename = Some(s.value()),
+                            ref lit => return Err(Error::new_spanned(lit, "expected a string")),
+                        }
+                    }
+                    NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.path.is_ident("index") => {
+                        match nv.lit {
+                            Lit::Int(ref i) => field_attrs.index = Some(i.base10_parse()?),
+                            ref lit => return Err(Error::new_spanned(lit, "expected an integer")),
+                        }
+                    }
+                    nested => {
+                        return Err(Error::new_spanned(nested, "unknown rusqlite attribute"));
+                    }
+                }
+            }
+        }
+        Ok(field_attrs)
+    }
+}
+
+fn struct_fields(input: &DeriveInput) -> syn::Result<&Fields> {
+    match input.data {
+        Data::Struct(ref data) => Ok(&data.fields),
+        _ => Err(Error::new_spanned(
+            &input.ident,
+            "only structs are supported by rusqlite derive macros",
+        )),
+    }
+}
+
+/// Name of a named field, without the `r#` prefix of raw identifiers.
+fn field_name(field: &Field) -> Option<String> {
+    field.ident.as_ref().map(|ident| {
+        let name = ident.to_string();
+        name.trim_start_matches("r#").to_owned()
+    })
+}
+
+fn from_row(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = struct_fields(input)?;
+    let mut values = Vec::with_capacity(fields.len());
+    // column index of the next tuple struct field
+    let mut column = 0usize;
+    for field in fields.iter() {
+        let attrs = FieldAttrs::parse(&field.attrs)?;
+        let value = if attrs.skip {
+            quote!(::std::default::Default::default())
+        } else {
+            let idx = match (attrs.index, attrs.rename, field_name(field)) {
+                (Some(index), _, _) => quote!(#index),
+                (None, Some(name), _) | (None, None, Some(name)) => quote!(#name),
+                (None, None, None) => quote!(#column),
+            };
+            column += 1;
+            if attrs.default {
+                quote! {
+                    match row.get(#idx) {
+                        Err(::rusqlite::Error::InvalidColumnName(_))
+                        | Err(::rusqlite::Error::InvalidColumnIndex(_)) => {
+                            ::std::default::Default::default()
+                        }
+                        value => value?,
+                    }
+                }
+            } else {
+                quote!(row.get(#idx)?)
+            }
+        };
+        values.push(value);
+    }
+    let body = match *fields {
+        Fields::Named(_) => {
+            let idents = fields.iter().map(|field| &field.ident);
+            quote!(Self { #(#idents: #values),* })
+        }
+        Fields::Unnamed(_) => quote!(Self(#(#values),*)),
+        Fields::Unit => quote!(Self),
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::rusqlite::FromRow for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn from_row(row: &::rusqlite::Row<'_>) -> ::rusqlite::Result<Self> {
+                Ok(#body)
+            }
+        }
+    })
+}
+
+fn to_params(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = struct_fields(input)?;
+    let mut binds = Vec::with_capacity(fields.len());
+    // one-based position of the next tuple struct parameter
+    let mut position = 0usize;
+    for (i, field) in fields.iter().enumerate() {
+        let attrs = FieldAttrs::parse(&field.attrs)?;
+        if attrs.skip {
+            continue;
+        }
+        position += 1;
+        let member = match field.ident {
+            Some(ref ident) => quote!(#ident),
+            None => {
+                let index = Index::from(i);
+                quote!(#index)
+            }
+        };
+        let bind = match (attrs.rename, field_name(field)) {
+            (Some(name), _) | (None, Some(name)) => {
+                let names = [":", "@", "$"]
+                    .iter()
+                    .map(|prefix| format!("{}{}", prefix, name));
+                quote! {
+                    for name in &[#(#names),*] {
+                        if let Some(index) = stmt.parameter_index(name)? {
+                            stmt.raw_bind_parameter(index, &self.#member)?;
+                        }
+                    }
+                }
+            }
+            (None, None) => quote!(stmt.raw_bind_parameter(#position, &self.#member)?;),
+        };
+        binds.push(bind);
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::rusqlite::ToParams for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn bind_params(
+                &self,
+                stmt: &mut ::rusqlite::Statement<'_>,
+            ) -> ::rusqlite::Result<()> {
+                #(#binds)*
+                Ok(())
+            }
+        }
+    })
+}
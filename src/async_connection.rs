@@ -0,0 +1,512 @@
+//! Run a `Connection` on a dedicated worker thread, from async code.
+//!
+//! An [`AsyncConnection`](struct.AsyncConnection.html) owns a `Connection`
+//! on a background thread. Closures passed to
+//! [`call`](struct.AsyncConnection.html#method.call) are run there, one at a
+//! time, and their result is delivered through a `Future`. Only `std` is
+//! used, so any executor can drive these futures.
+//!
+//! ```rust,no_run
+//! # use rusqlite::async_connection::AsyncConnection;
+//! # use rusqlite::{Result, NO_PARAMS};
+//! async fn count_people(conn: &AsyncConnection) -> Result<i64> {
+//!     conn.call(|conn| conn.query_row("SELECT count(*) FROM people", NO_PARAMS, |r| r.get(0)))
+//!         .await
+//! }
+//! ```
+use std::fmt;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+use crate::ffi;
+use crate::{Connection, Error, InterruptHandle, Result, Transaction, TransactionBehavior};
+
+type Job = Box<dyn FnOnce(&mut Connection) + Send + 'static>;
+
+enum Message {
+    Call(Job),
+    Close(SlotGuard<()>),
+}
+
+/// A `Connection` running on a dedicated worker thread.
+///
+/// Cloning an `AsyncConnection` is cheap: clones share the same worker
+/// thread and connection. The worker thread exits, closing the connection,
+/// once all clones have been dropped and all pending calls have completed.
+#[derive(Clone)]
+pub struct AsyncConnection {
+    sender: Sender<Message>,
+    interrupt: Arc<InterruptHandle>,
+}
+
+impl AsyncConnection {
+    /// Move `conn` to a new worker thread.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the worker thread cannot be spawned.
+    pub fn new(mut conn: Connection) -> Result<AsyncConnection> {
+        let interrupt = Arc::new(conn.get_interrupt_handle());
+        let (sender, receiver) = mpsc::channel::<Message>();
+        thread::Builder::new()
+            .name("rusqlite-worker".to_owned())
+            .spawn(move || {
+                while let Ok(message) = receiver.recv() {
+                    match message {
+                        Message::Call(job) => job(&mut conn),
+                        Message::Close(slot) => match conn.close() {
+                            Ok(()) => {
+                                slot.0.complete(Ok(Ok(())));
+                                // Messages still queued are dropped with the
+                                // receiver, failing their calls.
+                                return;
+                            }
+                            Err((c, err)) => {
+                                conn = c;
+                                slot.0.complete(Ok(Err(err)));
+                            }
+                        },
+                    }
+                }
+            })
+            .map_err(|err| {
+                Error::SqliteFailure(
+                    ffi::Error::new(ffi::SQLITE_ERROR),
+                    Some(format!("cannot spawn worker thread: {}", err)),
+                )
+            })?;
+        Ok(AsyncConnection { sender, interrupt })
+    }
+
+    /// Open a new connection to a SQLite database on a new worker thread.
+    /// See `Connection::open`.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the connection cannot be opened or if the worker
+    /// thread cannot be spawned.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<AsyncConnection> {
+        Connection::open(path).and_then(AsyncConnection::new)
+    }
+
+    /// Open a new connection to an in-memory SQLite database on a new worker
+    /// thread.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the connection cannot be opened or if the worker
+    /// thread cannot be spawned.
+    pub fn open_in_memory() -> Result<AsyncConnection> {
+        Connection::open_in_memory().and_then(AsyncConnection::new)
+    }
+
+    /// Run `f` with the connection on the worker thread.
+    ///
+    /// Calls are run in the order they are made. Dropping the returned
+    /// future before it completes cancels the call: it is skipped if it has
+    /// not started yet, and interrupted (see `InterruptHandle`) otherwise.
+    ///
+    /// If `f` panics, the panic is resumed when the future is polled.
+    ///
+    /// # Failure
+    ///
+    /// The future resolves to `Err` if `f` fails, or if the connection has
+    /// been closed.
+    pub fn call<F, T>(&self, f: F) -> CallFuture<T>
+    where
+        F: FnOnce(&mut Connection) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let slot = Arc::new(Slot::new());
+        let worker_slot = SlotGuard(slot.clone());
+        let job: Job = Box::new(move |conn| {
+            let worker_slot = &worker_slot.0;
+            if !worker_slot.start() {
+                // cancelled
+                return;
+            }
+            let result = panic::catch_unwind(AssertUnwindSafe(|| f(conn)));
+            worker_slot.complete(result);
+        });
+        self.send(Message::Call(job), slot)
+    }
+
+    /// Run `f` in a deferred transaction on the worker thread.
+    ///
+    /// The transaction is committed if `f` succeeds, and rolled back
+    /// otherwise. See [`call`](#method.call).
+    pub fn transaction<F, T>(&self, f: F) -> CallFuture<T>
+    where
+        F: FnOnce(&Transaction<'_>) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.transaction_with_behavior(TransactionBehavior::Deferred, f)
+    }
+
+    /// Run `f` in a transaction of the given behavior on the worker thread.
+    ///
+    /// The transaction is committed if `f` succeeds, and rolled back
+    /// otherwise. See [`call`](#method.call).
+    pub fn transaction_with_behavior<F, T>(
+        &self,
+        behavior: TransactionBehavior,
+        f: F,
+    ) -> CallFuture<T>
+    where
+        F: FnOnce(&Transaction<'_>) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.call(move |conn| {
+            let tx = conn.transaction_with_behavior(behavior)?;
+            let value = f(&tx)?;
+            tx.commit()?;
+            Ok(value)
+        })
+    }
+
+    /// Interrupt the call currently running on the worker thread, if any.
+    /// See `InterruptHandle::interrupt`.
+    pub fn interrupt(&self) {
+        self.interrupt.interrupt();
+    }
+
+    /// Close the connection once the pending calls have completed, and stop
+    /// the worker thread. Later calls (from clones of this `AsyncConnection`)
+    /// fail.
+    ///
+    /// # Failure
+    ///
+    /// The future resolves to `Err` if the connection cannot be closed, in
+    /// which case it stays open.
+    pub fn close(self) -> CallFuture<()> {
+        let slot = Arc::new(Slot::new());
+        self.send(Message::Close(SlotGuard(slot.clone())), slot)
+    }
+
+    fn send<T>(&self, message: Message, slot: Arc<Slot<T>>) -> CallFuture<T> {
+        // If the worker is gone, the message is dropped right away and its
+        // `SlotGuard` fails the call.
+        let _ = self.sender.send(message);
+        CallFuture {
+            slot,
+            interrupt: self.interrupt.clone(),
+        }
+    }
+}
+
+impl fmt::Debug for AsyncConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncConnection").finish()
+    }
+}
+
+enum State<T> {
+    Queued,
+    Running,
+    Done(thread::Result<Result<T>>),
+    Cancelled,
+    Taken,
+}
+
+/// Result of a call, shared by the worker thread and the future.
+struct Slot<T> {
+    state: Mutex<(State<T>, Option<Waker>)>,
+}
+
+impl<T> Slot<T> {
+    fn new() -> Slot<T> {
+        Slot {
+            state: Mutex::new((State::Queued, None)),
+        }
+    }
+
+    /// Mark the call as running, unless it has been cancelled.
+    fn start(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match state.0 {
+            State::Queued => {
+                state.0 = State::Running;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Fail the call if it has neither run nor been cancelled.
+    fn abandon(&self) {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+        if let State::Queued = state.0 {
+            state.0 = State::Done(Ok(Err(Error::SqliteFailure(
+                ffi::Error::new(ffi::SQLITE_MISUSE),
+                Some("connection closed".to_owned()),
+            ))));
+            if let Some(waker) = state.1.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    fn complete(&self, result: thread::Result<Result<T>>) {
+        let mut state = self.state.lock().unwrap();
+        if let State::Cancelled = state.0 {
+            return;
+        }
+        state.0 = State::Done(result);
+        if let Some(waker) = state.1.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Worker side of a `Slot`: completes the call with an error if it is
+/// dropped before the call is run, e.g. when it is still queued once the
+/// connection has been closed.
+struct SlotGuard<T>(Arc<Slot<T>>);
+
+impl<T> Drop for SlotGuard<T> {
+    fn drop(&mut self) {
+        self.0.abandon();
+    }
+}
+
+/// Future returned by [`AsyncConnection::call`](struct.AsyncConnection.html#method.call).
+#[must_use = "futures do nothing unless polled"]
+pub struct CallFuture<T> {
+    slot: Arc<Slot<T>>,
+    interrupt: Arc<InterruptHandle>,
+}
+
+impl<T> Future for CallFuture<T> {
+    type Output = Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<T>> {
+        let mut state = self.slot.state.lock().unwrap();
+        match std::mem::replace(&mut state.0, State::Taken) {
+            State::Done(Ok(result)) => Poll::Ready(result),
+            State::Done(Err(payload)) => {
+                drop(state);
+                panic::resume_unwind(payload)
+            }
+            State::Taken => panic!("CallFuture polled after completion"),
+            pending => {
+                state.0 = pending;
+                state.1 = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> Drop for CallFuture<T> {
+    fn drop(&mut self) {
+        let mut state = match self.slot.state.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+        match state.0 {
+            State::Queued => state.0 = State::Cancelled,
+            State::Running => {
+                state.0 = State::Cancelled;
+                // while the slot is locked, the worker cannot move on to the
+                // next call.
+                self.interrupt.interrupt();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<T> fmt::Debug for CallFuture<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallFuture").finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+    use std::thread::{self, Thread};
+    use std::time::Duration;
+
+    use super::AsyncConnection;
+    use crate::{Error, ErrorCode, NO_PARAMS};
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(mut future: F) -> F::Output {
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        // Safety: `future` is not moved after being pinned.
+        let mut future = unsafe { Pin::new_unchecked(&mut future) };
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    fn error_code(err: Error) -> ErrorCode {
+        match err {
+            Error::SqliteFailure(err, _) => err.code,
+            err => panic!("unexpected error {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_call() {
+        let conn = AsyncConnection::open_in_memory().unwrap();
+        block_on(conn.call(|conn| conn.execute_batch("CREATE TABLE foo(x INTEGER)"))).unwrap();
+        let worker = block_on(conn.call(|_| Ok(thread::current().id()))).unwrap();
+        assert_ne!(thread::current().id(), worker);
+
+        let clone = conn.clone();
+        let inserted = block_on(async move {
+            clone
+                .call(|conn| conn.execute("INSERT INTO foo VALUES (42)", NO_PARAMS))
+                .await
+        })
+        .unwrap();
+        assert_eq!(1, inserted);
+        let x: i64 = block_on(
+            conn.call(|conn| conn.query_row("SELECT x FROM foo", NO_PARAMS, |r| r.get(0))),
+        )
+        .unwrap();
+        assert_eq!(42, x);
+    }
+
+    #[test]
+    fn test_transaction() {
+        let conn = AsyncConnection::open_in_memory().unwrap();
+        block_on(conn.call(|conn| conn.execute_batch("CREATE TABLE foo(x INTEGER)"))).unwrap();
+
+        block_on(conn.transaction(|tx| tx.execute("INSERT INTO foo VALUES (1)", NO_PARAMS)))
+            .unwrap();
+        block_on(conn.transaction(|tx| {
+            tx.execute("INSERT INTO foo VALUES (2)", NO_PARAMS)?;
+            Err::<(), _>(Error::QueryReturnedNoRows)
+        }))
+        .unwrap_err();
+
+        let sum: i64 = block_on(
+            conn.call(|conn| conn.query_row("SELECT sum(x) FROM foo", NO_PARAMS, |r| r.get(0))),
+        )
+        .unwrap();
+        assert_eq!(1, sum);
+    }
+
+    #[test]
+    fn test_cancel() {
+        let conn = AsyncConnection::open_in_memory().unwrap();
+        let (started_tx, started_rx) = mpsc::channel();
+        let running = conn.call(move |conn| {
+            started_tx.send(()).unwrap();
+            conn.query_row(
+                "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT count(*) FROM c",
+                NO_PARAMS,
+                |r| r.get::<_, i64>(0),
+            )
+        });
+        // queued behind `running`, never run
+        let ran = Arc::new(AtomicBool::new(false));
+        let queued = conn.call({
+            let ran = ran.clone();
+            move |_| {
+                ran.store(true, Ordering::SeqCst);
+                Ok(())
+            }
+        });
+
+        started_rx.recv().unwrap();
+        thread::sleep(Duration::from_millis(10));
+        drop(queued);
+        drop(running);
+
+        // the worker is available again
+        let one: i64 =
+            block_on(conn.call(|conn| conn.query_row("SELECT 1", NO_PARAMS, |r| r.get(0))))
+                .unwrap();
+        assert_eq!(1, one);
+        assert!(!ran.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_interrupt() {
+        let conn = AsyncConnection::open_in_memory().unwrap();
+        let (started_tx, started_rx) = mpsc::channel();
+        let running = conn.call(move |conn| {
+            started_tx.send(()).unwrap();
+            conn.query_row(
+                "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT count(*) FROM c",
+                NO_PARAMS,
+                |r| r.get::<_, i64>(0),
+            )
+        });
+        started_rx.recv().unwrap();
+        thread::sleep(Duration::from_millis(10));
+        conn.interrupt();
+        let err = block_on(running).unwrap_err();
+        assert_eq!(ErrorCode::OperationInterrupted, error_code(err));
+    }
+
+    #[test]
+    fn test_panic() {
+        let conn = AsyncConnection::open_in_memory().unwrap();
+        let result = thread::spawn({
+            let conn = conn.clone();
+            move || block_on(conn.call(|_| -> crate::Result<()> { panic!("boom") }))
+        })
+        .join();
+        assert!(result.is_err());
+        // the worker survives
+        block_on(conn.call(|_| Ok(()))).unwrap();
+    }
+
+    #[test]
+    fn test_close() {
+        let conn = AsyncConnection::open_in_memory().unwrap();
+        let clone = conn.clone();
+        block_on(conn.close()).unwrap();
+        let err = block_on(clone.call(|_| Ok(()))).unwrap_err();
+        assert_eq!(ErrorCode::APIMisuse, error_code(err));
+    }
+
+    #[test]
+    fn test_call_queued_behind_close() {
+        let conn = AsyncConnection::open_in_memory().unwrap();
+        let clone = conn.clone();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let blocked = conn.call(move |_| {
+            release_rx.recv().unwrap();
+            Ok(())
+        });
+        let closed = conn.close();
+        let queued = clone.call(|_| Ok(()));
+        release_tx.send(()).unwrap();
+
+        block_on(blocked).unwrap();
+        block_on(closed).unwrap();
+        let err = block_on(queued).unwrap_err();
+        assert_eq!(ErrorCode::APIMisuse, error_code(err));
+    }
+}
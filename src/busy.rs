@@ -1,14 +1,36 @@
-///! Busy handler (when the database is locked)
+//! Busy handler (when the database is locked)
+//!
+//! Besides arbitrary closures, `Connection::busy_policy` accepts ready-made
+//! retry strategies: `ExponentialBackoff` spreads out the retries of
+//! competing writers, `Deadline` bounds the total time spent waiting, and
+//! `LogAndRetry` reports each attempt of another policy.
+//!
+//! ```rust,no_run
+//! # use rusqlite::{busy::{BusyPolicy, ExponentialBackoff}, Connection, Result};
+//! # use std::time::Duration;
+//! fn open(path: &str) -> Result<Connection> {
+//!     let conn = Connection::open(path)?;
+//!     let policy = ExponentialBackoff::new(Duration::from_millis(1), Duration::from_millis(100))
+//!         .max_retries(20)
+//!         .log(|count, retry| eprintln!("database busy ({}), retrying: {}", count, retry));
+//!     conn.busy_policy(policy)?;
+//!     Ok(conn)
+//! }
+//! ```
+use std::collections::hash_map::RandomState;
 use std::convert::TryInto;
-use std::mem;
+use std::hash::{BuildHasher, Hasher};
 use std::os::raw::{c_int, c_void};
-use std::panic::catch_unwind;
+use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::ptr;
-use std::time::Duration;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::ffi;
 use crate::{Connection, InnerConnection, Result};
 
+pub(crate) type BoxedBusyHandler = Box<dyn FnMut(i32) -> bool + Send + 'static>;
+
 impl Connection {
     /// Set a busy handler that sleeps for a specified amount of time when a
     /// table is locked. The handler will sleep multiple times until at
@@ -48,42 +70,228 @@ impl Connection {
     /// handler. Note that calling `busy_timeout()` or evaluating `PRAGMA
     /// busy_timeout=N` will change the busy handler and thus
     /// clear any previously set busy handler.
-    pub fn busy_handler(&self, callback: Option<fn(i32) -> bool>) -> Result<()> {
-        unsafe extern "C" fn busy_handler_callback(p_arg: *mut c_void, count: c_int) -> c_int {
-            let handler_fn: fn(i32) -> bool = mem::transmute(p_arg);
-            if let Ok(true) = catch_unwind(|| handler_fn(count)) {
+    pub fn busy_handler<F>(&self, callback: Option<F>) -> Result<()>
+    where
+        F: FnMut(i32) -> bool + Send + 'static,
+    {
+        unsafe extern "C" fn call_boxed_closure(p_arg: *mut c_void, count: c_int) -> c_int {
+            let boxed_handler = p_arg as *mut BoxedBusyHandler;
+            if let Ok(true) = catch_unwind(AssertUnwindSafe(|| (*boxed_handler)(count))) {
                 1
             } else {
                 0
             }
         }
         let mut c = self.db.borrow_mut();
-        let r = match callback {
-            Some(f) => unsafe {
-                ffi::sqlite3_busy_handler(c.db(), Some(busy_handler_callback), mem::transmute(f))
-            },
-            None => unsafe { ffi::sqlite3_busy_handler(c.db(), None, ptr::null_mut()) },
-        };
-        c.decode_result(r)
+        match callback {
+            Some(f) => {
+                let mut boxed_handler: Box<BoxedBusyHandler> = Box::new(Box::new(f));
+                let r = unsafe {
+                    ffi::sqlite3_busy_handler(
+                        c.db(),
+                        Some(call_boxed_closure),
+                        &mut *boxed_handler as *mut BoxedBusyHandler as *mut _,
+                    )
+                };
+                c.decode_result(r)?;
+                c.busy_handler = Some(boxed_handler);
+            }
+            None => {
+                let r = unsafe { ffi::sqlite3_busy_handler(c.db(), None, ptr::null_mut()) };
+                c.decode_result(r)?;
+                c.busy_handler = None;
+            }
+        }
+        Ok(())
+    }
+
+    /// Register `policy` as the busy handler of this connection.
+    ///
+    /// This is a shorthand for `busy_handler` with a closure calling
+    /// `BusyPolicy::retry`, and therefore also clears any previously set
+    /// busy handler or timeout.
+    pub fn busy_policy<P: BusyPolicy>(&self, mut policy: P) -> Result<()> {
+        self.busy_handler(Some(move |count| policy.retry(count)))
     }
 }
 
 impl InnerConnection {
     fn busy_timeout(&mut self, timeout: c_int) -> Result<()> {
         let r = unsafe { ffi::sqlite3_busy_timeout(self.db, timeout) };
-        self.decode_result(r)
+        self.decode_result(r)?;
+        // SQLite no longer references the previous handler, if any.
+        self.busy_handler = None;
+        Ok(())
+    }
+}
+
+/// A strategy deciding whether, and after which delay, to retry an
+/// operation which failed with `SQLITE_BUSY`.
+pub trait BusyPolicy: Send + 'static {
+    /// Called each time the database is found locked. `count` is the number
+    /// of times this method has been invoked previously for the same locking
+    /// event.
+    ///
+    /// Implementations should sleep before returning `true` to let SQLite
+    /// retry, or return `false` to make it give up with `SQLITE_BUSY`.
+    fn retry(&mut self, count: i32) -> bool;
+
+    /// Wraps this policy so that `log` is called with the `count` and the
+    /// outcome of each `retry`.
+    fn log<F>(self, log: F) -> LogAndRetry<Self, F>
+    where
+        Self: Sized,
+        F: FnMut(i32, bool) + Send + 'static,
+    {
+        LogAndRetry { policy: self, log }
+    }
+}
+
+/// Retry with exponentially increasing delays.
+///
+/// The `n`th retry of a locking event sleeps for a random duration between
+/// half and all of `initial * 2^n`, capped at `max_delay`. The jitter keeps
+/// several connections waiting on the same lock from retrying in lockstep.
+#[derive(Clone, Copy, Debug)]
+pub struct ExponentialBackoff {
+    initial: Duration,
+    max_delay: Duration,
+    max_retries: Option<i32>,
+}
+
+impl ExponentialBackoff {
+    /// Creates a policy that retries forever, starting with delays of about
+    /// `initial` and never sleeping longer than `max_delay`.
+    pub fn new(initial: Duration, max_delay: Duration) -> ExponentialBackoff {
+        ExponentialBackoff {
+            initial,
+            max_delay,
+            max_retries: None,
+        }
+    }
+
+    /// Give up after `max_retries` retries of the same locking event.
+    pub fn max_retries(mut self, max_retries: i32) -> ExponentialBackoff {
+        self.max_retries = Some(max_retries);
+        self
+    }
+
+    fn delay(&self, count: i32) -> Duration {
+        let factor = 1u32.checked_shl(count as u32).unwrap_or(u32::MAX);
+        let base = self
+            .initial
+            .checked_mul(factor)
+            .map_or(self.max_delay, |d| d.min(self.max_delay));
+        let half = base / 2;
+        let spread = (base - half).as_nanos() as u64;
+        if spread == 0 {
+            return base;
+        }
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_i32(count);
+        half + Duration::from_nanos(hasher.finish() % (spread + 1))
+    }
+}
+
+impl BusyPolicy for ExponentialBackoff {
+    fn retry(&mut self, count: i32) -> bool {
+        match self.max_retries {
+            Some(max) if count >= max => return false,
+            _ => {}
+        }
+        thread::sleep(self.delay(count));
+        true
+    }
+}
+
+/// Retry at a fixed interval until `timeout` has elapsed since the lock was
+/// first encountered.
+///
+/// Unlike `busy_timeout`, which only counts the time spent sleeping, the
+/// deadline also accounts for the time spent in the retries themselves.
+#[derive(Clone, Copy, Debug)]
+pub struct Deadline {
+    timeout: Duration,
+    interval: Duration,
+    started: Option<Instant>,
+}
+
+impl Deadline {
+    /// Creates a policy that gives up after `timeout`, polling every
+    /// millisecond.
+    pub fn new(timeout: Duration) -> Deadline {
+        Deadline {
+            timeout,
+            interval: Duration::from_millis(1),
+            started: None,
+        }
+    }
+
+    /// Sleep for `interval` between retries.
+    pub fn interval(mut self, interval: Duration) -> Deadline {
+        self.interval = interval;
+        self
+    }
+}
+
+impl BusyPolicy for Deadline {
+    fn retry(&mut self, count: i32) -> bool {
+        let now = Instant::now();
+        let started = match self.started {
+            Some(started) if count > 0 => started,
+            _ => {
+                self.started = Some(now);
+                now
+            }
+        };
+        let elapsed = now.duration_since(started);
+        if elapsed >= self.timeout {
+            return false;
+        }
+        thread::sleep(self.interval.min(self.timeout - elapsed));
+        true
+    }
+}
+
+/// Reports each decision of another policy, see `BusyPolicy::log`.
+#[derive(Clone, Copy, Debug)]
+pub struct LogAndRetry<P, F> {
+    policy: P,
+    log: F,
+}
+
+impl<P, F> BusyPolicy for LogAndRetry<P, F>
+where
+    P: BusyPolicy,
+    F: FnMut(i32, bool) + Send + 'static,
+{
+    fn retry(&mut self, count: i32) -> bool {
+        let retry = self.policy.retry(count);
+        (self.log)(count, retry);
+        retry
     }
 }
 
 #[cfg(test)]
 mod test {
-    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
     use std::sync::mpsc::sync_channel;
+    use std::sync::Arc;
     use std::thread;
-    use std::time::Duration;
+    use std::time::{Duration, Instant};
 
+    use super::{BusyPolicy, Deadline, ExponentialBackoff};
     use crate::{Connection, Error, ErrorCode, Result, TransactionBehavior, NO_PARAMS};
 
+    fn assert_busy(r: Result<i32>) {
+        match r.unwrap_err() {
+            Error::SqliteFailure(err, _) => {
+                assert_eq!(err.code, ErrorCode::DatabaseBusy);
+            }
+            err => panic!("Unexpected error {}", err),
+        }
+    }
+
     #[test]
     fn test_default_busy() {
         let temp_dir = tempfile::tempdir().unwrap();
@@ -173,4 +381,77 @@ mod test {
 
         child.join().unwrap();
     }
+
+    #[test]
+    fn test_busy_handler_closure() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("test.db3");
+
+        let mut db1 = Connection::open(&path).unwrap();
+        let tx1 = db1
+            .transaction_with_behavior(TransactionBehavior::Exclusive)
+            .unwrap();
+        let db2 = Connection::open(&path).unwrap();
+        let calls = Arc::new(AtomicI32::new(0));
+        let counter = calls.clone();
+        db2.busy_handler(Some(move |count| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            count < 2
+        }))
+        .unwrap();
+
+        assert_busy(db2.query_row("PRAGMA schema_version", NO_PARAMS, |r| r.get(0)));
+        assert_eq!(3, calls.load(Ordering::Relaxed));
+
+        db2.busy_timeout(Duration::from_millis(0)).unwrap();
+        assert!(db2.db.borrow().busy_handler.is_none());
+        assert_busy(db2.query_row("PRAGMA schema_version", NO_PARAMS, |r| r.get(0)));
+        assert_eq!(3, calls.load(Ordering::Relaxed));
+        tx1.rollback().unwrap();
+    }
+
+    #[test]
+    fn test_busy_policy() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("test.db3");
+
+        let mut db1 = Connection::open(&path).unwrap();
+        let tx1 = db1
+            .transaction_with_behavior(TransactionBehavior::Exclusive)
+            .unwrap();
+        let db2 = Connection::open(&path).unwrap();
+        let gave_up = Arc::new(AtomicBool::new(false));
+        let logged = gave_up.clone();
+        let policy = Deadline::new(Duration::from_millis(50))
+            .interval(Duration::from_millis(5))
+            .log(move |count, retry| {
+                assert!(count >= 0);
+                if !retry {
+                    logged.store(true, Ordering::Relaxed);
+                }
+            });
+        db2.busy_policy(policy).unwrap();
+
+        let start = Instant::now();
+        assert_busy(db2.query_row("PRAGMA schema_version", NO_PARAMS, |r| r.get(0)));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert!(gave_up.load(Ordering::Relaxed));
+        tx1.rollback().unwrap();
+    }
+
+    #[test]
+    fn test_exponential_backoff() {
+        let max_delay = Duration::from_millis(40);
+        let mut policy =
+            ExponentialBackoff::new(Duration::from_millis(1), max_delay).max_retries(3);
+        for count in 0..10 {
+            let base = Duration::from_millis(1 << count).min(max_delay);
+            let delay = policy.delay(count);
+            assert!(delay >= base / 2 && delay <= base, "{:?}", delay);
+        }
+        let huge = ExponentialBackoff::new(Duration::from_secs(1), max_delay);
+        assert!(huge.delay(i32::MAX) <= max_delay);
+        assert!(policy.retry(2));
+        assert!(!policy.retry(3));
+    }
 }
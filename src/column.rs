@@ -1,3 +1,5 @@
+#[cfg(feature = "column_metadata")]
+use std::ffi::CStr;
 use std::str;
 
 use crate::{Error, Result, Row, Rows, Statement};
@@ -7,6 +9,12 @@ use crate::{Error, Result, Row, Rows, Statement};
 pub struct Column<'stmt> {
     name: &'stmt str,
     decl_type: Option<&'stmt str>,
+    #[cfg(feature = "column_metadata")]
+    database_name: Option<&'stmt str>,
+    #[cfg(feature = "column_metadata")]
+    table_name: Option<&'stmt str>,
+    #[cfg(feature = "column_metadata")]
+    origin_name: Option<&'stmt str>,
 }
 
 impl Column<'_> {
@@ -19,6 +27,28 @@ impl Column<'_> {
     pub fn decl_type(&self) -> Option<&str> {
         self.decl_type
     }
+
+    /// Returns the name of the database (`main`, `temp` or the name of an
+    /// attached database) of the table column this result column comes from
+    /// (`None` for expression).
+    #[cfg(feature = "column_metadata")]
+    pub fn database_name(&self) -> Option<&str> {
+        self.database_name
+    }
+
+    /// Returns the name of the table this column comes from (`None` for
+    /// expression).
+    #[cfg(feature = "column_metadata")]
+    pub fn table_name(&self) -> Option<&str> {
+        self.table_name
+    }
+
+    /// Returns the name of the table column this column comes from, before
+    /// any `AS` renaming (`None` for expression).
+    #[cfg(feature = "column_metadata")]
+    pub fn origin_name(&self) -> Option<&str> {
+        self.origin_name
+    }
 }
 
 impl Statement<'_> {
@@ -95,12 +125,26 @@ impl Statement<'_> {
             let decl_type = slice.map(|s| {
                 str::from_utf8(s.to_bytes()).expect("Invalid UTF-8 sequence in column declaration")
             });
-            cols.push(Column { name, decl_type });
+            cols.push(Column {
+                name,
+                decl_type,
+                #[cfg(feature = "column_metadata")]
+                database_name: self.stmt.column_database_name(i).map(utf8),
+                #[cfg(feature = "column_metadata")]
+                table_name: self.stmt.column_table_name(i).map(utf8),
+                #[cfg(feature = "column_metadata")]
+                origin_name: self.stmt.column_origin_name(i).map(utf8),
+            });
         }
         cols
     }
 }
 
+#[cfg(feature = "column_metadata")]
+fn utf8(s: &CStr) -> &str {
+    str::from_utf8(s.to_bytes()).expect("Invalid UTF-8 sequence in column metadata")
+}
+
 impl<'stmt> Rows<'stmt> {
     /// Get all the column names.
     pub fn column_names(&self) -> Option<Vec<&str>> {
@@ -177,6 +221,24 @@ mod test {
         );
     }
 
+    #[test]
+    #[cfg(feature = "column_metadata")]
+    fn test_column_metadata() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch("CREATE TABLE foo(x INTEGER, y TEXT);")
+            .unwrap();
+        let query = db
+            .prepare("SELECT x AS renamed, y, 1 + 1 FROM foo")
+            .unwrap();
+        let columns = query.columns();
+        let database_names: Vec<_> = columns.iter().map(Column::database_name).collect();
+        assert_eq!(vec![Some("main"), Some("main"), None], database_names);
+        let table_names: Vec<_> = columns.iter().map(Column::table_name).collect();
+        assert_eq!(vec![Some("foo"), Some("foo"), None], table_names);
+        let origin_names: Vec<_> = columns.iter().map(Column::origin_name).collect();
+        assert_eq!(vec![Some("x"), Some("y"), None], origin_names);
+    }
+
     #[test]
     fn test_column_name_in_error() {
         use crate::{types::Type, Error};
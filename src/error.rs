@@ -103,6 +103,21 @@ pub enum Error {
 
     /// Error when the SQL contains multiple statements.
     MultipleStatement,
+
+    /// Error when an operation was interrupted because the progress handler
+    /// (see `Connection::progress_handler`) returned `true`.
+    #[cfg(feature = "hooks")]
+    ProgressHandlerInterrupted,
+
+    /// Error returned by `migrations::Migrations` when the database schema
+    /// cannot be migrated.
+    #[cfg(feature = "migrations")]
+    MigrationError(crate::migrations::MigrationError),
+
+    /// Error returned by `Row::deserialize` when the row does not have the
+    /// shape of the requested type (e.g. too few columns for a tuple).
+    #[cfg(feature = "serde")]
+    DeserializeError(String),
 }
 
 impl PartialEq for Error {
@@ -142,6 +157,12 @@ impl PartialEq for Error {
             (Error::UnwindingPanic, Error::UnwindingPanic) => true,
             #[cfg(feature = "functions")]
             (Error::GetAuxWrongType, Error::GetAuxWrongType) => true,
+            #[cfg(feature = "hooks")]
+            (Error::ProgressHandlerInterrupted, Error::ProgressHandlerInterrupted) => true,
+            #[cfg(feature = "migrations")]
+            (Error::MigrationError(e1), Error::MigrationError(e2)) => e1 == e2,
+            #[cfg(feature = "serde")]
+            (Error::DeserializeError(s1), Error::DeserializeError(s2)) => s1 == s2,
             (..) => false,
         }
     }
@@ -248,6 +269,14 @@ impl fmt::Display for Error {
             #[cfg(feature = "functions")]
             Error::GetAuxWrongType => write!(f, "get_aux called with wrong type"),
             Error::MultipleStatement => write!(f, "Multiple statements provided"),
+            #[cfg(feature = "hooks")]
+            Error::ProgressHandlerInterrupted => {
+                write!(f, "Operation interrupted by the progress handler")
+            }
+            #[cfg(feature = "migrations")]
+            Error::MigrationError(ref err) => err.fmt(f),
+            #[cfg(feature = "serde")]
+            Error::DeserializeError(ref desc) => write!(f, "{}", desc),
         }
     }
 }
@@ -291,6 +320,12 @@ impl error::Error for Error {
             #[cfg(feature = "functions")]
             Error::GetAuxWrongType => "get_aux called with wrong type",
             Error::MultipleStatement => "multiple statements provided",
+            #[cfg(feature = "hooks")]
+            Error::ProgressHandlerInterrupted => "operation interrupted by the progress handler",
+            #[cfg(feature = "migrations")]
+            Error::MigrationError(_) => "migration error",
+            #[cfg(feature = "serde")]
+            Error::DeserializeError(ref desc) => desc,
         }
     }
 
@@ -332,6 +367,15 @@ impl error::Error for Error {
 
             #[cfg(feature = "functions")]
             Error::GetAuxWrongType => None,
+
+            #[cfg(feature = "hooks")]
+            Error::ProgressHandlerInterrupted => None,
+
+            #[cfg(feature = "migrations")]
+            Error::MigrationError(ref err) => Some(err),
+
+            #[cfg(feature = "serde")]
+            Error::DeserializeError(_) => None,
         }
     }
 }
@@ -115,6 +115,40 @@ unsafe extern "C" fn free_boxed_value<T>(p: *mut c_void) {
     drop(Box::from_raw(p as *mut T));
 }
 
+/// Invoke the boxed scalar function stored as the user data of `ctx`.
+pub(crate) unsafe extern "C" fn call_boxed_closure<F, T>(
+    ctx: *mut sqlite3_context,
+    argc: c_int,
+    argv: *mut *mut sqlite3_value,
+) where
+    F: FnMut(&Context<'_>) -> Result<T>,
+    T: ToSql,
+{
+    let r = catch_unwind(|| {
+        let boxed_f: *mut F = ffi::sqlite3_user_data(ctx) as *mut F;
+        assert!(!boxed_f.is_null(), "Internal error - null function pointer");
+        let ctx = Context {
+            ctx,
+            args: slice::from_raw_parts(argv, argc as usize),
+        };
+        (*boxed_f)(&ctx)
+    });
+    let t = match r {
+        Err(_) => {
+            report_error(ctx, &Error::UnwindingPanic);
+            return;
+        }
+        Ok(r) => r,
+    };
+    let t = t.as_ref().map(|t| ToSql::to_sql(t));
+
+    match t {
+        Ok(Ok(ref value)) => set_result(ctx, value),
+        Ok(Err(err)) => report_error(ctx, &err),
+        Err(err) => report_error(ctx, err),
+    }
+}
+
 /// Context is a wrapper for the SQLite function evaluation context.
 pub struct Context<'a> {
     ctx: *mut sqlite3_context,
@@ -386,39 +420,6 @@ impl InnerConnection {
         F: FnMut(&Context<'_>) -> Result<T> + Send + UnwindSafe + 'static,
         T: ToSql,
     {
-        unsafe extern "C" fn call_boxed_closure<F, T>(
-            ctx: *mut sqlite3_context,
-            argc: c_int,
-            argv: *mut *mut sqlite3_value,
-        ) where
-            F: FnMut(&Context<'_>) -> Result<T>,
-            T: ToSql,
-        {
-            let r = catch_unwind(|| {
-                let boxed_f: *mut F = ffi::sqlite3_user_data(ctx) as *mut F;
-                assert!(!boxed_f.is_null(), "Internal error - null function pointer");
-                let ctx = Context {
-                    ctx,
-                    args: slice::from_raw_parts(argv, argc as usize),
-                };
-                (*boxed_f)(&ctx)
-            });
-            let t = match r {
-                Err(_) => {
-                    report_error(ctx, &Error::UnwindingPanic);
-                    return;
-                }
-                Ok(r) => r,
-            };
-            let t = t.as_ref().map(|t| ToSql::to_sql(t));
-
-            match t {
-                Ok(Ok(ref value)) => set_result(ctx, value),
-                Ok(Err(err)) => report_error(ctx, &err),
-                Err(err) => report_error(ctx, err),
-            }
-        }
-
         let boxed_f: *mut F = Box::into_raw(Box::new(x_func));
         let c_name = str_to_cstring(fn_name)?;
         let r = unsafe {
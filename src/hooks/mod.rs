@@ -0,0 +1,1194 @@
+//! Commit, Data Change, Rollback, Write-Ahead Log Notification, Progress
+//! Handler and Authorizer Callbacks
+#![allow(non_camel_case_types)]
+
+use std::ffi::CStr;
+use std::mem::MaybeUninit;
+use std::os::raw::{c_char, c_int, c_void};
+use std::panic::catch_unwind;
+use std::ptr;
+use std::str;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use crate::ffi;
+
+use crate::{Connection, DatabaseName, InnerConnection, Result};
+
+#[cfg(feature = "preupdate_hook")]
+mod preupdate_hook;
+#[cfg(feature = "preupdate_hook")]
+pub use preupdate_hook::{PreUpdateCase, PreUpdateNewValueAccessor, PreUpdateOldValueAccessor};
+
+/// Action Codes
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(i32)]
+pub enum Action {
+    UNKNOWN = -1,
+    SQLITE_DELETE = ffi::SQLITE_DELETE,
+    SQLITE_INSERT = ffi::SQLITE_INSERT,
+    SQLITE_UPDATE = ffi::SQLITE_UPDATE,
+}
+
+impl From<i32> for Action {
+    fn from(code: i32) -> Action {
+        match code {
+            ffi::SQLITE_DELETE => Action::SQLITE_DELETE,
+            ffi::SQLITE_INSERT => Action::SQLITE_INSERT,
+            ffi::SQLITE_UPDATE => Action::SQLITE_UPDATE,
+            _ => Action::UNKNOWN,
+        }
+    }
+}
+
+/// The context received by an authorizer callback.
+///
+/// See [sqlite3_set_authorizer](https://sqlite.org/c3ref/set_authorizer.html)
+/// for more info.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AuthContext<'c> {
+    /// The action to be authorized.
+    pub action: AuthAction<'c>,
+    /// The database name ("main", "temp", ...), if applicable.
+    pub database_name: Option<&'c str>,
+    /// The inner-most trigger or view responsible for the access attempt,
+    /// or `None` if the access attempt was made by top-level SQL code.
+    pub accessor: Option<&'c str>,
+}
+
+/// Actions (and their arguments) found within a statement during
+/// preparation.
+///
+/// See [Authorizer Action Codes](https://sqlite.org/c3ref/c_alter_table.html).
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[allow(missing_docs)]
+pub enum AuthAction<'c> {
+    /// An action code unknown to rusqlite, with its raw arguments.
+    Unknown {
+        code: i32,
+        arg1: Option<&'c str>,
+        arg2: Option<&'c str>,
+    },
+    CreateIndex {
+        index_name: &'c str,
+        table_name: &'c str,
+    },
+    CreateTable {
+        table_name: &'c str,
+    },
+    CreateTempIndex {
+        index_name: &'c str,
+        table_name: &'c str,
+    },
+    CreateTempTable {
+        table_name: &'c str,
+    },
+    CreateTempTrigger {
+        trigger_name: &'c str,
+        table_name: &'c str,
+    },
+    CreateTempView {
+        view_name: &'c str,
+    },
+    CreateTrigger {
+        trigger_name: &'c str,
+        table_name: &'c str,
+    },
+    CreateView {
+        view_name: &'c str,
+    },
+    Delete {
+        table_name: &'c str,
+    },
+    DropIndex {
+        index_name: &'c str,
+        table_name: &'c str,
+    },
+    DropTable {
+        table_name: &'c str,
+    },
+    DropTempIndex {
+        index_name: &'c str,
+        table_name: &'c str,
+    },
+    DropTempTable {
+        table_name: &'c str,
+    },
+    DropTempTrigger {
+        trigger_name: &'c str,
+        table_name: &'c str,
+    },
+    DropTempView {
+        view_name: &'c str,
+    },
+    DropTrigger {
+        trigger_name: &'c str,
+        table_name: &'c str,
+    },
+    DropView {
+        view_name: &'c str,
+    },
+    Insert {
+        table_name: &'c str,
+    },
+    Pragma {
+        pragma_name: &'c str,
+        /// The pragma value, if present (e.g., `PRAGMA name = value`).
+        pragma_value: Option<&'c str>,
+    },
+    Read {
+        table_name: &'c str,
+        column_name: &'c str,
+    },
+    Select,
+    Transaction {
+        operation: TransactionOperation,
+    },
+    Update {
+        table_name: &'c str,
+        column_name: &'c str,
+    },
+    Attach {
+        filename: &'c str,
+    },
+    Detach {
+        database_name: &'c str,
+    },
+    AlterTable {
+        database_name: &'c str,
+        table_name: &'c str,
+    },
+    Reindex {
+        index_name: &'c str,
+    },
+    Analyze {
+        table_name: &'c str,
+    },
+    CreateVtable {
+        table_name: &'c str,
+        module_name: &'c str,
+    },
+    DropVtable {
+        table_name: &'c str,
+        module_name: &'c str,
+    },
+    Function {
+        function_name: &'c str,
+    },
+    Savepoint {
+        operation: TransactionOperation,
+        savepoint_name: &'c str,
+    },
+    Recursive,
+}
+
+impl<'c> AuthAction<'c> {
+    fn from_raw(code: i32, arg1: Option<&'c str>, arg2: Option<&'c str>) -> AuthAction<'c> {
+        match (code, arg1, arg2) {
+            (ffi::SQLITE_CREATE_INDEX, Some(index_name), Some(table_name)) => {
+                AuthAction::CreateIndex {
+                    index_name,
+                    table_name,
+                }
+            }
+            (ffi::SQLITE_CREATE_TABLE, Some(table_name), _) => {
+                AuthAction::CreateTable { table_name }
+            }
+            (ffi::SQLITE_CREATE_TEMP_INDEX, Some(index_name), Some(table_name)) => {
+                AuthAction::CreateTempIndex {
+                    index_name,
+                    table_name,
+                }
+            }
+            (ffi::SQLITE_CREATE_TEMP_TABLE, Some(table_name), _) => {
+                AuthAction::CreateTempTable { table_name }
+            }
+            (ffi::SQLITE_CREATE_TEMP_TRIGGER, Some(trigger_name), Some(table_name)) => {
+                AuthAction::CreateTempTrigger {
+                    trigger_name,
+                    table_name,
+                }
+            }
+            (ffi::SQLITE_CREATE_TEMP_VIEW, Some(view_name), _) => {
+                AuthAction::CreateTempView { view_name }
+            }
+            (ffi::SQLITE_CREATE_TRIGGER, Some(trigger_name), Some(table_name)) => {
+                AuthAction::CreateTrigger {
+                    trigger_name,
+                    table_name,
+                }
+            }
+            (ffi::SQLITE_CREATE_VIEW, Some(view_name), _) => AuthAction::CreateView { view_name },
+            (ffi::SQLITE_DELETE, Some(table_name), _) => AuthAction::Delete { table_name },
+            (ffi::SQLITE_DROP_INDEX, Some(index_name), Some(table_name)) => AuthAction::DropIndex {
+                index_name,
+                table_name,
+            },
+            (ffi::SQLITE_DROP_TABLE, Some(table_name), _) => AuthAction::DropTable { table_name },
+            (ffi::SQLITE_DROP_TEMP_INDEX, Some(index_name), Some(table_name)) => {
+                AuthAction::DropTempIndex {
+                    index_name,
+                    table_name,
+                }
+            }
+            (ffi::SQLITE_DROP_TEMP_TABLE, Some(table_name), _) => {
+                AuthAction::DropTempTable { table_name }
+            }
+            (ffi::SQLITE_DROP_TEMP_TRIGGER, Some(trigger_name), Some(table_name)) => {
+                AuthAction::DropTempTrigger {
+                    trigger_name,
+                    table_name,
+                }
+            }
+            (ffi::SQLITE_DROP_TEMP_VIEW, Some(view_name), _) => {
+                AuthAction::DropTempView { view_name }
+            }
+            (ffi::SQLITE_DROP_TRIGGER, Some(trigger_name), Some(table_name)) => {
+                AuthAction::DropTrigger {
+                    trigger_name,
+                    table_name,
+                }
+            }
+            (ffi::SQLITE_DROP_VIEW, Some(view_name), _) => AuthAction::DropView { view_name },
+            (ffi::SQLITE_INSERT, Some(table_name), _) => AuthAction::Insert { table_name },
+            (ffi::SQLITE_PRAGMA, Some(pragma_name), pragma_value) => AuthAction::Pragma {
+                pragma_name,
+                pragma_value,
+            },
+            (ffi::SQLITE_READ, Some(table_name), Some(column_name)) => AuthAction::Read {
+                table_name,
+                column_name,
+            },
+            (ffi::SQLITE_SELECT, ..) => AuthAction::Select,
+            (ffi::SQLITE_TRANSACTION, Some(operation_str), _) => AuthAction::Transaction {
+                operation: TransactionOperation::from_str(operation_str),
+            },
+            (ffi::SQLITE_UPDATE, Some(table_name), Some(column_name)) => AuthAction::Update {
+                table_name,
+                column_name,
+            },
+            (ffi::SQLITE_ATTACH, Some(filename), _) => AuthAction::Attach { filename },
+            (ffi::SQLITE_DETACH, Some(database_name), _) => AuthAction::Detach { database_name },
+            (ffi::SQLITE_ALTER_TABLE, Some(database_name), Some(table_name)) => {
+                AuthAction::AlterTable {
+                    database_name,
+                    table_name,
+                }
+            }
+            (ffi::SQLITE_REINDEX, Some(index_name), _) => AuthAction::Reindex { index_name },
+            (ffi::SQLITE_ANALYZE, Some(table_name), _) => AuthAction::Analyze { table_name },
+            (ffi::SQLITE_CREATE_VTABLE, Some(table_name), Some(module_name)) => {
+                AuthAction::CreateVtable {
+                    table_name,
+                    module_name,
+                }
+            }
+            (ffi::SQLITE_DROP_VTABLE, Some(table_name), Some(module_name)) => {
+                AuthAction::DropVtable {
+                    table_name,
+                    module_name,
+                }
+            }
+            (ffi::SQLITE_FUNCTION, _, Some(function_name)) => {
+                AuthAction::Function { function_name }
+            }
+            (ffi::SQLITE_SAVEPOINT, Some(operation_str), Some(savepoint_name)) => {
+                AuthAction::Savepoint {
+                    operation: TransactionOperation::from_str(operation_str),
+                    savepoint_name,
+                }
+            }
+            // SQLITE_RECURSIVE: 3.8.3
+            (33, ..) => AuthAction::Recursive,
+            (code, arg1, arg2) => AuthAction::Unknown { code, arg1, arg2 },
+        }
+    }
+}
+
+/// A transaction (or savepoint) operation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransactionOperation {
+    /// An operation unknown to rusqlite.
+    Unknown,
+    /// `BEGIN` (or `SAVEPOINT`)
+    Begin,
+    /// `COMMIT` (or `END`)
+    Commit,
+    /// `RELEASE`
+    Release,
+    /// `ROLLBACK`
+    Rollback,
+}
+
+impl TransactionOperation {
+    fn from_str(op_str: &str) -> TransactionOperation {
+        match op_str {
+            "BEGIN" => TransactionOperation::Begin,
+            "COMMIT" => TransactionOperation::Commit,
+            "RELEASE" => TransactionOperation::Release,
+            "ROLLBACK" => TransactionOperation::Rollback,
+            _ => TransactionOperation::Unknown,
+        }
+    }
+}
+
+/// Authorizer return code
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Authorization {
+    /// Authorize the action.
+    Allow,
+    /// Don't allow access, but don't trigger an error either: a column read
+    /// returns NULL, a row deletion is silently skipped, ...
+    Ignore,
+    /// Cause the entire SQL statement to be rejected with an error.
+    Deny,
+}
+
+impl Authorization {
+    fn into_raw(self) -> c_int {
+        match self {
+            Authorization::Allow => ffi::SQLITE_OK,
+            Authorization::Ignore => ffi::SQLITE_IGNORE,
+            Authorization::Deny => ffi::SQLITE_DENY,
+        }
+    }
+}
+
+/// Checkpoint mode, see
+/// [sqlite3_wal_checkpoint_v2](https://sqlite.org/c3ref/wal_checkpoint_v2.html).
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(i32)]
+pub enum CheckpointMode {
+    /// Checkpoint as many frames as possible without waiting for any database
+    /// readers or writers to finish.
+    Passive = ffi::SQLITE_CHECKPOINT_PASSIVE,
+    /// Block until there is no database writer and all readers are reading
+    /// from the most recent database snapshot, then checkpoint all frames.
+    Full = ffi::SQLITE_CHECKPOINT_FULL,
+    /// Like `Full`, but also wait until all readers are reading from the
+    /// database file only, so that the next writer restarts the log file from
+    /// the beginning.
+    Restart = ffi::SQLITE_CHECKPOINT_RESTART,
+    /// Like `Restart`, but also truncate the log file to zero bytes.
+    Truncate = 3, // 3.8.8
+}
+
+/// Outcome of a `Connection::wal_checkpoint` call.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CheckpointStatus {
+    /// Total number of frames in the log file, or -1 if the database is not
+    /// in WAL mode.
+    pub log_size: i32,
+    /// Total number of frames in the log file that have been checkpointed, or
+    /// -1 if the database is not in WAL mode.
+    pub checkpointed: i32,
+}
+
+pub(crate) type BoxedProgressHandler = Box<dyn FnMut() -> bool + Send + 'static>;
+
+/// A registered progress handler and the number of virtual machine
+/// instructions between two of its invocations.
+pub(crate) type ProgressHandler = (c_int, Box<BoxedProgressHandler>);
+
+pub(crate) type BoxedAuthorizer =
+    Box<dyn for<'c> FnMut(AuthContext<'c>) -> Authorization + Send + 'static>;
+
+impl Connection {
+    /// Register a callback function to be invoked whenever a transaction is
+    /// committed.
+    ///
+    /// The callback returns `true` to rollback.
+    pub fn commit_hook<F>(&self, hook: Option<F>)
+    where
+        F: FnMut() -> bool + Send + 'static,
+    {
+        self.db.borrow_mut().commit_hook(hook);
+    }
+
+    /// Register a callback function to be invoked whenever a transaction is
+    /// committed.
+    ///
+    /// The callback returns `true` to rollback.
+    pub fn rollback_hook<F>(&self, hook: Option<F>)
+    where
+        F: FnMut() + Send + 'static,
+    {
+        self.db.borrow_mut().rollback_hook(hook);
+    }
+
+    /// Register a callback function to be invoked whenever a row is updated,
+    /// inserted or deleted in a rowid table.
+    ///
+    /// The callback parameters are:
+    ///
+    /// - the type of database update (SQLITE_INSERT, SQLITE_UPDATE or
+    /// SQLITE_DELETE),
+    /// - the name of the database ("main", "temp", ...),
+    /// - the name of the table that is updated,
+    /// - the ROWID of the row that is updated.
+    pub fn update_hook<F>(&self, hook: Option<F>)
+    where
+        F: FnMut(Action, &str, &str, i64) + Send + 'static,
+    {
+        self.db.borrow_mut().update_hook(hook);
+    }
+
+    /// Register a callback function to be invoked each time data is committed
+    /// to a database in WAL mode.
+    ///
+    /// The callback parameters are:
+    ///
+    /// - the name of the database that was written to ("main", "temp", ...),
+    /// - the number of pages currently in the write-ahead log file.
+    ///
+    /// The callback cannot access the connection that invoked it, but it can
+    /// for example notify another thread that a checkpoint should be run
+    /// (see `wal_checkpoint`).
+    ///
+    /// Note that registering a WAL hook disables the automatic checkpoints
+    /// configured by `PRAGMA wal_autocheckpoint`, and that setting
+    /// `wal_autocheckpoint` overrides the WAL hook.
+    pub fn wal_hook<F>(&self, hook: Option<F>)
+    where
+        F: FnMut(&str, usize) + Send + 'static,
+    {
+        self.db.borrow_mut().wal_hook(hook);
+    }
+
+    /// Run a checkpoint on the database named `db_name`, which must be in WAL
+    /// mode.
+    ///
+    /// Returns the size of the log file and the number of checkpointed frames
+    /// (both -1 if the database is not in WAL mode).
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the checkpoint could not complete, e.g. if another
+    /// connection was writing (`SQLITE_BUSY`) for the `Full`, `Restart` and
+    /// `Truncate` modes.
+    pub fn wal_checkpoint(
+        &self,
+        db_name: DatabaseName<'_>,
+        mode: CheckpointMode,
+    ) -> Result<CheckpointStatus> {
+        self.db.borrow_mut().wal_checkpoint(db_name, mode)
+    }
+
+    /// Register a callback to be invoked periodically during long running
+    /// calls to `execute`, `query`, `execute_batch`, ...
+    ///
+    /// The callback is invoked approximately every `num_ops` virtual machine
+    /// instructions. If it returns `true`, the operation is interrupted and
+    /// fails with `Error::ProgressHandlerInterrupted`. This can be used to
+    /// enforce a step or time budget from the connection's own thread (see
+    /// also `Statement::query_with_deadline`); use `InterruptHandle` to
+    /// interrupt a query from another thread.
+    ///
+    /// If `num_ops` is less than one or `handler` is `None`, the progress
+    /// handler is disabled.
+    pub fn progress_handler<F>(&self, num_ops: i32, handler: Option<F>)
+    where
+        F: FnMut() -> bool + Send + 'static,
+    {
+        self.db.borrow_mut().progress_handler(num_ops, handler);
+    }
+
+    /// Register an authorizer callback to be invoked as SQL statements are
+    /// being compiled by `prepare` (or `execute`, `query_row`, ...).
+    ///
+    /// The callback is given an `AuthContext` describing the action to be
+    /// authorized and returns `Allow`, `Ignore` or `Deny`. Statements that
+    /// were prepared before the authorizer was registered are not affected
+    /// until they are re-prepared, which may happen when the statement
+    /// cache is flushed.
+    ///
+    /// If the callback panics, the action is denied.
+    ///
+    /// Passing `None` disables authorization.
+    pub fn authorizer<F>(&self, hook: Option<F>)
+    where
+        F: for<'c> FnMut(AuthContext<'c>) -> Authorization + Send + 'static,
+    {
+        self.db.borrow_mut().authorizer(hook);
+    }
+}
+
+impl InnerConnection {
+    pub fn remove_hooks(&mut self) {
+        self.update_hook(None::<fn(Action, &str, &str, i64)>);
+        self.commit_hook(None::<fn() -> bool>);
+        self.rollback_hook(None::<fn()>);
+        self.wal_hook(None::<fn(&str, usize)>);
+        self.set_progress_handler(None);
+        self.authorizer(None::<fn(AuthContext<'_>) -> Authorization>);
+        #[cfg(feature = "preupdate_hook")]
+        self.preupdate_hook(None::<fn(Action, &str, &str, &PreUpdateCase)>);
+    }
+
+    fn commit_hook<F>(&mut self, hook: Option<F>)
+    where
+        F: FnMut() -> bool + Send + 'static,
+    {
+        unsafe extern "C" fn call_boxed_closure<F>(p_arg: *mut c_void) -> c_int
+        where
+            F: FnMut() -> bool,
+        {
+            let r = catch_unwind(|| {
+                let boxed_hook: *mut F = p_arg as *mut F;
+                (*boxed_hook)()
+            });
+            if let Ok(true) = r {
+                1
+            } else {
+                0
+            }
+        }
+
+        // unlike `sqlite3_create_function_v2`, we cannot specify a `xDestroy` with
+        // `sqlite3_commit_hook`. so we keep the `xDestroy` function in
+        // `InnerConnection.free_boxed_hook`.
+        let free_commit_hook = if hook.is_some() {
+            Some(free_boxed_hook::<F> as fn(*mut c_void))
+        } else {
+            None
+        };
+
+        let previous_hook = match hook {
+            Some(hook) => {
+                let boxed_hook: *mut F = Box::into_raw(Box::new(hook));
+                unsafe {
+                    ffi::sqlite3_commit_hook(
+                        self.db(),
+                        Some(call_boxed_closure::<F>),
+                        boxed_hook as *mut _,
+                    )
+                }
+            }
+            _ => unsafe { ffi::sqlite3_commit_hook(self.db(), None, ptr::null_mut()) },
+        };
+        if !previous_hook.is_null() {
+            if let Some(free_boxed_hook) = self.free_commit_hook {
+                free_boxed_hook(previous_hook);
+            }
+        }
+        self.free_commit_hook = free_commit_hook;
+    }
+
+    fn rollback_hook<F>(&mut self, hook: Option<F>)
+    where
+        F: FnMut() + Send + 'static,
+    {
+        unsafe extern "C" fn call_boxed_closure<F>(p_arg: *mut c_void)
+        where
+            F: FnMut(),
+        {
+            let _ = catch_unwind(|| {
+                let boxed_hook: *mut F = p_arg as *mut F;
+                (*boxed_hook)();
+            });
+        }
+
+        let free_rollback_hook = if hook.is_some() {
+            Some(free_boxed_hook::<F> as fn(*mut c_void))
+        } else {
+            None
+        };
+
+        let previous_hook = match hook {
+            Some(hook) => {
+                let boxed_hook: *mut F = Box::into_raw(Box::new(hook));
+                unsafe {
+                    ffi::sqlite3_rollback_hook(
+                        self.db(),
+                        Some(call_boxed_closure::<F>),
+                        boxed_hook as *mut _,
+                    )
+                }
+            }
+            _ => unsafe { ffi::sqlite3_rollback_hook(self.db(), None, ptr::null_mut()) },
+        };
+        if !previous_hook.is_null() {
+            if let Some(free_boxed_hook) = self.free_rollback_hook {
+                free_boxed_hook(previous_hook);
+            }
+        }
+        self.free_rollback_hook = free_rollback_hook;
+    }
+
+    fn update_hook<F>(&mut self, hook: Option<F>)
+    where
+        F: FnMut(Action, &str, &str, i64) + Send + 'static,
+    {
+        unsafe extern "C" fn call_boxed_closure<F>(
+            p_arg: *mut c_void,
+            action_code: c_int,
+            db_str: *const c_char,
+            tbl_str: *const c_char,
+            row_id: i64,
+        ) where
+            F: FnMut(Action, &str, &str, i64),
+        {
+            let action = Action::from(action_code);
+            let db_name = {
+                let c_slice = CStr::from_ptr(db_str).to_bytes();
+                str::from_utf8_unchecked(c_slice)
+            };
+            let tbl_name = {
+                let c_slice = CStr::from_ptr(tbl_str).to_bytes();
+                str::from_utf8_unchecked(c_slice)
+            };
+
+            let _ = catch_unwind(|| {
+                let boxed_hook: *mut F = p_arg as *mut F;
+                (*boxed_hook)(action, db_name, tbl_name, row_id);
+            });
+        }
+
+        let free_update_hook = if hook.is_some() {
+            Some(free_boxed_hook::<F> as fn(*mut c_void))
+        } else {
+            None
+        };
+
+        let previous_hook = match hook {
+            Some(hook) => {
+                let boxed_hook: *mut F = Box::into_raw(Box::new(hook));
+                unsafe {
+                    ffi::sqlite3_update_hook(
+                        self.db(),
+                        Some(call_boxed_closure::<F>),
+                        boxed_hook as *mut _,
+                    )
+                }
+            }
+            _ => unsafe { ffi::sqlite3_update_hook(self.db(), None, ptr::null_mut()) },
+        };
+        if !previous_hook.is_null() {
+            if let Some(free_boxed_hook) = self.free_update_hook {
+                free_boxed_hook(previous_hook);
+            }
+        }
+        self.free_update_hook = free_update_hook;
+    }
+
+    fn wal_hook<F>(&mut self, hook: Option<F>)
+    where
+        F: FnMut(&str, usize) + Send + 'static,
+    {
+        unsafe extern "C" fn call_boxed_closure<F>(
+            p_arg: *mut c_void,
+            _: *mut ffi::sqlite3,
+            db_str: *const c_char,
+            pages: c_int,
+        ) -> c_int
+        where
+            F: FnMut(&str, usize),
+        {
+            let db_name = {
+                let c_slice = CStr::from_ptr(db_str).to_bytes();
+                str::from_utf8_unchecked(c_slice)
+            };
+
+            let _ = catch_unwind(|| {
+                let boxed_hook: *mut F = p_arg as *mut F;
+                (*boxed_hook)(db_name, pages as usize);
+            });
+            ffi::SQLITE_OK
+        }
+
+        let free_wal_hook = if hook.is_some() {
+            Some(free_boxed_hook::<F> as fn(*mut c_void))
+        } else {
+            None
+        };
+
+        let previous_hook = match hook {
+            Some(hook) => {
+                let boxed_hook: *mut F = Box::into_raw(Box::new(hook));
+                unsafe {
+                    ffi::sqlite3_wal_hook(
+                        self.db(),
+                        Some(call_boxed_closure::<F>),
+                        boxed_hook as *mut _,
+                    )
+                }
+            }
+            _ => unsafe { ffi::sqlite3_wal_hook(self.db(), None, ptr::null_mut()) },
+        };
+        if !previous_hook.is_null() {
+            if let Some(free_boxed_hook) = self.free_wal_hook {
+                free_boxed_hook(previous_hook);
+            }
+        }
+        self.free_wal_hook = free_wal_hook;
+    }
+
+    fn wal_checkpoint(
+        &mut self,
+        db_name: DatabaseName<'_>,
+        mode: CheckpointMode,
+    ) -> Result<CheckpointStatus> {
+        let db_name = db_name.to_cstring()?;
+        let mut log_size = MaybeUninit::uninit();
+        let mut checkpointed = MaybeUninit::uninit();
+        let r = unsafe {
+            ffi::sqlite3_wal_checkpoint_v2(
+                self.db(),
+                db_name.as_ptr(),
+                mode as c_int,
+                log_size.as_mut_ptr(),
+                checkpointed.as_mut_ptr(),
+            )
+        };
+        self.decode_result(r)?;
+        Ok(unsafe {
+            CheckpointStatus {
+                log_size: log_size.assume_init(),
+                checkpointed: checkpointed.assume_init(),
+            }
+        })
+    }
+
+    /// Register `handler` as the progress handler, returning the previous
+    /// one so that it can be restored with `set_progress_handler`.
+    pub(crate) fn progress_handler<F>(
+        &mut self,
+        num_ops: c_int,
+        handler: Option<F>,
+    ) -> Option<ProgressHandler>
+    where
+        F: FnMut() -> bool + Send + 'static,
+    {
+        let handler = handler.map(|mut handler| {
+            // Remember that the next `SQLITE_INTERRUPT` comes from the progress
+            // handler (see `decode_result`).
+            let interrupted = Arc::clone(&self.progress_interrupted);
+            let boxed_handler: BoxedProgressHandler = Box::new(move || {
+                let interrupt = handler();
+                if interrupt {
+                    interrupted.store(true, Ordering::Relaxed);
+                }
+                interrupt
+            });
+            (num_ops, Box::new(boxed_handler))
+        });
+        self.set_progress_handler(handler)
+    }
+
+    pub(crate) fn set_progress_handler(
+        &mut self,
+        handler: Option<ProgressHandler>,
+    ) -> Option<ProgressHandler> {
+        unsafe extern "C" fn call_boxed_closure(p_arg: *mut c_void) -> c_int {
+            let r = catch_unwind(|| {
+                let boxed_handler: *mut BoxedProgressHandler = p_arg as *mut BoxedProgressHandler;
+                (*boxed_handler)()
+            });
+            if let Ok(true) = r {
+                1
+            } else {
+                0
+            }
+        }
+
+        // `sqlite3_progress_handler` does not give back the previous user
+        // data, so the boxed closure is kept in
+        // `InnerConnection.progress_handler`.
+        match handler {
+            Some((num_ops, ref boxed_handler)) => unsafe {
+                ffi::sqlite3_progress_handler(
+                    self.db(),
+                    num_ops,
+                    Some(call_boxed_closure),
+                    &**boxed_handler as *const BoxedProgressHandler as *mut _,
+                )
+            },
+            _ => unsafe { ffi::sqlite3_progress_handler(self.db(), 0, None, ptr::null_mut()) },
+        }
+        self.progress_interrupted.store(false, Ordering::Relaxed);
+        std::mem::replace(&mut self.progress_handler, handler)
+    }
+
+    fn authorizer<F>(&mut self, hook: Option<F>)
+    where
+        F: for<'c> FnMut(AuthContext<'c>) -> Authorization + Send + 'static,
+    {
+        unsafe extern "C" fn call_boxed_closure<F>(
+            p_arg: *mut c_void,
+            action_code: c_int,
+            param1: *const c_char,
+            param2: *const c_char,
+            db_name: *const c_char,
+            trigger_or_view_name: *const c_char,
+        ) -> c_int
+        where
+            F: for<'c> FnMut(AuthContext<'c>) -> Authorization,
+        {
+            let r = catch_unwind(|| {
+                let auth_ctx = AuthContext {
+                    action: AuthAction::from_raw(
+                        action_code,
+                        optional_str(param1),
+                        optional_str(param2),
+                    ),
+                    database_name: optional_str(db_name),
+                    accessor: optional_str(trigger_or_view_name),
+                };
+                let boxed_hook: *mut F = p_arg as *mut F;
+                (*boxed_hook)(auth_ctx)
+            });
+            match r {
+                Ok(authorization) => authorization.into_raw(),
+                Err(_) => ffi::SQLITE_DENY,
+            }
+        }
+
+        // `sqlite3_set_authorizer` does not give back the previous user data,
+        // so the boxed closure is kept in `InnerConnection.authorizer` (and
+        // only dropped once SQLite has been told to stop using it).
+        let r = match hook {
+            Some(hook) => {
+                let mut boxed_hook = Box::new(hook);
+                let r = unsafe {
+                    ffi::sqlite3_set_authorizer(
+                        self.db(),
+                        Some(call_boxed_closure::<F>),
+                        &mut *boxed_hook as *mut F as *mut _,
+                    )
+                };
+                self.authorizer = Some(boxed_hook);
+                r
+            }
+            _ => {
+                let r = unsafe { ffi::sqlite3_set_authorizer(self.db(), None, ptr::null_mut()) };
+                self.authorizer = None;
+                r
+            }
+        };
+        // `sqlite3_set_authorizer` can only fail with `SQLITE_MISUSE` when
+        // given an invalid database handle.
+        debug_assert_eq!(r, ffi::SQLITE_OK);
+    }
+}
+
+unsafe fn optional_str<'a>(p_str: *const c_char) -> Option<&'a str> {
+    if p_str.is_null() {
+        None
+    } else {
+        str::from_utf8(CStr::from_ptr(p_str).to_bytes()).ok()
+    }
+}
+
+fn free_boxed_hook<F>(p: *mut c_void) {
+    drop(unsafe { Box::from_raw(p as *mut F) });
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Action, AuthAction, AuthContext, Authorization, CheckpointMode};
+    use crate::{Connection, DatabaseName, Error, ErrorCode, NO_PARAMS};
+    use lazy_static::lazy_static;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[test]
+    fn test_commit_hook() {
+        let db = Connection::open_in_memory().unwrap();
+
+        lazy_static! {
+            static ref CALLED: AtomicBool = AtomicBool::new(false);
+        }
+        db.commit_hook(Some(|| {
+            CALLED.store(true, Ordering::Relaxed);
+            false
+        }));
+        db.execute_batch("BEGIN; CREATE TABLE foo (t TEXT); COMMIT;")
+            .unwrap();
+        assert!(CALLED.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_fn_commit_hook() {
+        let db = Connection::open_in_memory().unwrap();
+
+        fn hook() -> bool {
+            true
+        }
+
+        db.commit_hook(Some(hook));
+        db.execute_batch("BEGIN; CREATE TABLE foo (t TEXT); COMMIT;")
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_rollback_hook() {
+        let db = Connection::open_in_memory().unwrap();
+
+        lazy_static! {
+            static ref CALLED: AtomicBool = AtomicBool::new(false);
+        }
+        db.rollback_hook(Some(|| {
+            CALLED.store(true, Ordering::Relaxed);
+        }));
+        db.execute_batch("BEGIN; CREATE TABLE foo (t TEXT); ROLLBACK;")
+            .unwrap();
+        assert!(CALLED.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_update_hook() {
+        let db = Connection::open_in_memory().unwrap();
+
+        lazy_static! {
+            static ref CALLED: AtomicBool = AtomicBool::new(false);
+        }
+        db.update_hook(Some(|action, db: &str, tbl: &str, row_id| {
+            assert_eq!(Action::SQLITE_INSERT, action);
+            assert_eq!("main", db);
+            assert_eq!("foo", tbl);
+            assert_eq!(1, row_id);
+            CALLED.store(true, Ordering::Relaxed);
+        }));
+        db.execute_batch("CREATE TABLE foo (t TEXT)").unwrap();
+        db.execute_batch("INSERT INTO foo VALUES ('lisa')").unwrap();
+        assert!(CALLED.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_wal_hook() {
+        use std::sync::atomic::AtomicUsize;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("test.db3");
+        let db = Connection::open(&path).unwrap();
+        let mode: String = db
+            .query_row("PRAGMA journal_mode=WAL", NO_PARAMS, |row| row.get(0))
+            .unwrap();
+        assert_eq!("wal", mode);
+
+        lazy_static! {
+            static ref PAGES: AtomicUsize = AtomicUsize::new(0);
+        }
+        db.wal_hook(Some(|db: &str, pages| {
+            assert_eq!("main", db);
+            PAGES.store(pages, Ordering::Relaxed);
+        }));
+        db.execute_batch("CREATE TABLE foo (t TEXT); INSERT INTO foo VALUES ('lisa');")
+            .unwrap();
+        assert!(PAGES.load(Ordering::Relaxed) > 0);
+
+        let status = db
+            .wal_checkpoint(DatabaseName::Main, CheckpointMode::Truncate)
+            .unwrap();
+        assert_eq!(0, status.log_size);
+        assert_eq!(0, status.checkpointed);
+    }
+
+    #[test]
+    fn test_wal_checkpoint() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("test.db3");
+        let db = Connection::open(&path).unwrap();
+
+        // not in WAL mode
+        let status = db
+            .wal_checkpoint(DatabaseName::Main, CheckpointMode::Passive)
+            .unwrap();
+        assert_eq!(-1, status.log_size);
+        assert_eq!(-1, status.checkpointed);
+
+        db.query_row("PRAGMA journal_mode=WAL", NO_PARAMS, |_| Ok(()))
+            .unwrap();
+        db.execute_batch("PRAGMA wal_autocheckpoint=0; CREATE TABLE foo (t TEXT);")
+            .unwrap();
+        let status = db
+            .wal_checkpoint(DatabaseName::Main, CheckpointMode::Passive)
+            .unwrap();
+        assert!(status.log_size > 0);
+        assert_eq!(status.log_size, status.checkpointed);
+
+        db.wal_checkpoint(DatabaseName::Attached("missing"), CheckpointMode::Full)
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_authorizer() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch("CREATE TABLE foo (public TEXT, private TEXT)")
+            .unwrap();
+
+        let authorizer = move |ctx: AuthContext<'_>| match ctx.action {
+            AuthAction::Read {
+                column_name: "private",
+                ..
+            } => Authorization::Ignore,
+            AuthAction::DropTable { .. } => Authorization::Deny,
+            AuthAction::Pragma { .. } => panic!("shouldn't be called"),
+            _ => Authorization::Allow,
+        };
+
+        db.authorizer(Some(authorizer));
+        db.execute_batch(
+            "BEGIN TRANSACTION; INSERT INTO foo VALUES ('pub txt', 'priv txt'); COMMIT;",
+        )
+        .unwrap();
+        db.query_row_and_then("SELECT * FROM foo", NO_PARAMS, |row| -> crate::Result<()> {
+            assert_eq!(row.get::<_, String>("public")?, "pub txt");
+            assert!(row.get::<_, Option<String>>("private")?.is_none());
+            Ok(())
+        })
+        .unwrap();
+        db.execute_batch("DROP TABLE foo").unwrap_err();
+        db.execute_batch("PRAGMA user_version").unwrap_err();
+
+        db.authorizer(None::<fn(AuthContext<'_>) -> Authorization>);
+        db.execute_batch("PRAGMA user_version").unwrap();
+    }
+
+    #[test]
+    fn test_authorizer_context() {
+        use std::sync::{Arc, Mutex};
+
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch("CREATE TABLE foo (x INTEGER)").unwrap();
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen_clone = seen.clone();
+        db.authorizer(Some(move |ctx: AuthContext<'_>| {
+            if let AuthAction::Update {
+                table_name,
+                column_name,
+            } = ctx.action
+            {
+                seen_clone.lock().unwrap().push((
+                    table_name.to_owned(),
+                    column_name.to_owned(),
+                    ctx.database_name.map(str::to_owned),
+                ));
+            }
+            Authorization::Allow
+        }));
+        db.execute_batch("UPDATE foo SET x = 1").unwrap();
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![("foo".to_owned(), "x".to_owned(), Some("main".to_owned()))]
+        );
+    }
+
+    #[test]
+    fn test_progress_handler() {
+        use std::sync::atomic::AtomicUsize;
+
+        let db = Connection::open_in_memory().unwrap();
+
+        lazy_static! {
+            static ref CALLS: AtomicUsize = AtomicUsize::new(0);
+        }
+        db.progress_handler(
+            1,
+            Some(|| {
+                CALLS.fetch_add(1, Ordering::Relaxed);
+                false
+            }),
+        );
+        db.execute_batch("CREATE TABLE foo (t TEXT); INSERT INTO foo VALUES ('lisa');")
+            .unwrap();
+        assert!(CALLS.load(Ordering::Relaxed) > 0);
+
+        db.progress_handler(1, Some(|| true));
+        let err = db.execute_batch("SELECT * FROM foo").unwrap_err();
+        assert_eq!(Error::ProgressHandlerInterrupted, err);
+
+        db.progress_handler(0, None::<fn() -> bool>);
+        db.execute_batch("SELECT * FROM foo").unwrap();
+    }
+
+    #[test]
+    fn test_interrupt_is_not_progress_handler_interrupt() {
+        let db = Connection::open_in_memory().unwrap();
+        let interrupt_handle = db.get_interrupt_handle();
+        db.progress_handler(
+            1000,
+            Some(move || {
+                interrupt_handle.interrupt();
+                false
+            }),
+        );
+
+        let err = db
+            .query_row(
+                "WITH RECURSIVE c(x) AS (VALUES(1) UNION ALL SELECT x + 1 FROM c) \
+                 SELECT count(*) FROM c",
+                NO_PARAMS,
+                |row| row.get::<_, i64>(0),
+            )
+            .unwrap_err();
+        match err {
+            Error::SqliteFailure(err, _) => {
+                assert_eq!(ErrorCode::OperationInterrupted, err.code);
+            }
+            err => panic!("Unexpected error {}", err),
+        }
+    }
+
+    #[test]
+    fn test_query_with_deadline() {
+        use std::sync::atomic::AtomicUsize;
+        use std::time::{Duration, Instant};
+
+        let db = Connection::open_in_memory().unwrap();
+
+        lazy_static! {
+            static ref CALLS: AtomicUsize = AtomicUsize::new(0);
+        }
+        db.progress_handler(
+            1,
+            Some(|| {
+                CALLS.fetch_add(1, Ordering::Relaxed);
+                false
+            }),
+        );
+
+        let mut stmt = db
+            .prepare(
+                "WITH RECURSIVE c(x) AS (VALUES(1) UNION ALL SELECT x + 1 FROM c) \
+                 SELECT count(*) FROM c",
+            )
+            .unwrap();
+        let deadline = Instant::now() + Duration::from_millis(50);
+        let err = stmt
+            .query_with_deadline(NO_PARAMS, deadline, |mut rows| {
+                rows.next()?;
+                Ok(())
+            })
+            .unwrap_err();
+        assert_eq!(Error::ProgressHandlerInterrupted, err);
+        assert!(Instant::now() >= deadline);
+
+        // the previous handler is restored
+        let calls = CALLS.load(Ordering::Relaxed);
+        let mut stmt = db.prepare("SELECT 1").unwrap();
+        let n: i64 = stmt
+            .query_with_deadline(
+                NO_PARAMS,
+                Instant::now() + Duration::from_secs(60),
+                |mut rows| rows.next()?.unwrap().get(0),
+            )
+            .unwrap();
+        assert_eq!(1, n);
+        assert_eq!(calls, CALLS.load(Ordering::Relaxed));
+        db.execute_batch("SELECT 1").unwrap();
+        assert!(CALLS.load(Ordering::Relaxed) > calls);
+
+        // even if the closure panics
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut stmt = db.prepare("SELECT 1").unwrap();
+            stmt.query_with_deadline(NO_PARAMS, Instant::now(), |_| -> crate::Result<()> {
+                panic!("boom")
+            })
+        }));
+        assert!(result.is_err());
+        let calls = CALLS.load(Ordering::Relaxed);
+        db.execute_batch("SELECT 1").unwrap();
+        assert!(CALLS.load(Ordering::Relaxed) > calls);
+    }
+}
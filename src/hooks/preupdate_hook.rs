@@ -0,0 +1,329 @@
+//! Pre-update Hook
+use std::ffi::CStr;
+use std::mem::MaybeUninit;
+use std::os::raw::{c_char, c_int, c_void};
+use std::panic::catch_unwind;
+use std::ptr;
+use std::str;
+
+use super::{free_boxed_hook, Action};
+use crate::ffi;
+use crate::types::ValueRef;
+use crate::{Connection, InnerConnection, Result};
+
+/// The possible cases for when a pre-update hook gets triggered. Gives access
+/// to the values relevant for each case.
+pub enum PreUpdateCase {
+    /// A row is about to be inserted: only new values are available.
+    Insert(PreUpdateNewValueAccessor),
+    /// A row is about to be deleted: only old values are available.
+    Delete(PreUpdateOldValueAccessor),
+    /// A row is about to be updated: both old and new values are available.
+    Update {
+        old_value_accessor: PreUpdateOldValueAccessor,
+        new_value_accessor: PreUpdateNewValueAccessor,
+    },
+    /// An unknown kind of change.
+    Unknown,
+}
+
+impl From<&PreUpdateCase> for Action {
+    fn from(case: &PreUpdateCase) -> Action {
+        match *case {
+            PreUpdateCase::Insert(_) => Action::SQLITE_INSERT,
+            PreUpdateCase::Delete(_) => Action::SQLITE_DELETE,
+            PreUpdateCase::Update { .. } => Action::SQLITE_UPDATE,
+            PreUpdateCase::Unknown => Action::UNKNOWN,
+        }
+    }
+}
+
+/// Gives access to the old values (and rowid) of a row that is about to be
+/// updated or deleted.
+pub struct PreUpdateOldValueAccessor {
+    db: *mut ffi::sqlite3,
+    old_row_id: i64,
+}
+
+impl PreUpdateOldValueAccessor {
+    /// Number of columns in the row that is being modified.
+    pub fn count(&self) -> usize {
+        unsafe { ffi::sqlite3_preupdate_count(self.db) as usize }
+    }
+
+    /// Trigger depth: 0 if the change is caused by a top-level SQL statement,
+    /// 1 if it is caused by a trigger, 2 if it is caused by a trigger fired
+    /// by a trigger, ...
+    pub fn depth(&self) -> i32 {
+        unsafe { ffi::sqlite3_preupdate_depth(self.db) }
+    }
+
+    /// Rowid of the row before the change.
+    pub fn old_rowid(&self) -> i64 {
+        self.old_row_id
+    }
+
+    /// Value of the `i`th column of the row before the change.
+    pub fn old_value(&self, i: usize) -> Result<ValueRef<'_>> {
+        let mut p_value = MaybeUninit::uninit();
+        unsafe {
+            check!(ffi::sqlite3_preupdate_old(
+                self.db,
+                i as c_int,
+                p_value.as_mut_ptr()
+            ));
+            let p_value: *mut ffi::sqlite3_value = p_value.assume_init();
+            Ok(ValueRef::from_value(p_value))
+        }
+    }
+}
+
+/// Gives access to the new values (and rowid) of a row that is about to be
+/// inserted or updated.
+pub struct PreUpdateNewValueAccessor {
+    db: *mut ffi::sqlite3,
+    new_row_id: i64,
+}
+
+impl PreUpdateNewValueAccessor {
+    /// Number of columns in the row that is being modified.
+    pub fn count(&self) -> usize {
+        unsafe { ffi::sqlite3_preupdate_count(self.db) as usize }
+    }
+
+    /// Trigger depth: 0 if the change is caused by a top-level SQL statement,
+    /// 1 if it is caused by a trigger, 2 if it is caused by a trigger fired
+    /// by a trigger, ...
+    pub fn depth(&self) -> i32 {
+        unsafe { ffi::sqlite3_preupdate_depth(self.db) }
+    }
+
+    /// Rowid of the row after the change.
+    pub fn new_rowid(&self) -> i64 {
+        self.new_row_id
+    }
+
+    /// Value of the `i`th column of the row after the change.
+    pub fn new_value(&self, i: usize) -> Result<ValueRef<'_>> {
+        let mut p_value = MaybeUninit::uninit();
+        unsafe {
+            check!(ffi::sqlite3_preupdate_new(
+                self.db,
+                i as c_int,
+                p_value.as_mut_ptr()
+            ));
+            let p_value: *mut ffi::sqlite3_value = p_value.assume_init();
+            Ok(ValueRef::from_value(p_value))
+        }
+    }
+}
+
+impl Connection {
+    /// Register a callback function to be invoked before each row is
+    /// updated, inserted or deleted in a rowid table.
+    ///
+    /// The callback parameters are:
+    ///
+    /// - the type of database update (SQLITE_INSERT, SQLITE_UPDATE or
+    ///   SQLITE_DELETE),
+    /// - the name of the database ("main", "temp", ...),
+    /// - the name of the table that is updated,
+    /// - the old and/or new values (and rowids) of the row, depending on
+    ///   the type of update.
+    ///
+    /// Values can only be accessed while the callback is running.
+    pub fn preupdate_hook<F>(&self, hook: Option<F>)
+    where
+        F: FnMut(Action, &str, &str, &PreUpdateCase) + Send + 'static,
+    {
+        self.db.borrow_mut().preupdate_hook(hook);
+    }
+}
+
+impl InnerConnection {
+    pub(super) fn preupdate_hook<F>(&mut self, hook: Option<F>)
+    where
+        F: FnMut(Action, &str, &str, &PreUpdateCase) + Send + 'static,
+    {
+        unsafe extern "C" fn call_boxed_closure<F>(
+            p_arg: *mut c_void,
+            sqlite: *mut ffi::sqlite3,
+            action_code: c_int,
+            db_str: *const c_char,
+            tbl_str: *const c_char,
+            old_row_id: i64,
+            new_row_id: i64,
+        ) where
+            F: FnMut(Action, &str, &str, &PreUpdateCase),
+        {
+            let action = Action::from(action_code);
+            let db_name = {
+                let c_slice = CStr::from_ptr(db_str).to_bytes();
+                str::from_utf8_unchecked(c_slice)
+            };
+            let tbl_name = {
+                let c_slice = CStr::from_ptr(tbl_str).to_bytes();
+                str::from_utf8_unchecked(c_slice)
+            };
+
+            let preupdate_case = match action {
+                Action::SQLITE_INSERT => PreUpdateCase::Insert(PreUpdateNewValueAccessor {
+                    db: sqlite,
+                    new_row_id,
+                }),
+                Action::SQLITE_DELETE => PreUpdateCase::Delete(PreUpdateOldValueAccessor {
+                    db: sqlite,
+                    old_row_id,
+                }),
+                Action::SQLITE_UPDATE => PreUpdateCase::Update {
+                    old_value_accessor: PreUpdateOldValueAccessor {
+                        db: sqlite,
+                        old_row_id,
+                    },
+                    new_value_accessor: PreUpdateNewValueAccessor {
+                        db: sqlite,
+                        new_row_id,
+                    },
+                },
+                Action::UNKNOWN => PreUpdateCase::Unknown,
+            };
+
+            let _ = catch_unwind(|| {
+                let boxed_hook: *mut F = p_arg as *mut F;
+                (*boxed_hook)(action, db_name, tbl_name, &preupdate_case);
+            });
+        }
+
+        let free_preupdate_hook = if hook.is_some() {
+            Some(free_boxed_hook::<F> as fn(*mut c_void))
+        } else {
+            None
+        };
+
+        let previous_hook = match hook {
+            Some(hook) => {
+                let boxed_hook: *mut F = Box::into_raw(Box::new(hook));
+                unsafe {
+                    ffi::sqlite3_preupdate_hook(
+                        self.db(),
+                        Some(call_boxed_closure::<F>),
+                        boxed_hook as *mut _,
+                    )
+                }
+            }
+            _ => unsafe { ffi::sqlite3_preupdate_hook(self.db(), None, ptr::null_mut()) },
+        };
+        if !previous_hook.is_null() {
+            if let Some(free_boxed_hook) = self.free_preupdate_hook {
+                free_boxed_hook(previous_hook);
+            }
+        }
+        self.free_preupdate_hook = free_preupdate_hook;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use lazy_static::lazy_static;
+
+    use super::{Action, PreUpdateCase};
+    use crate::types::ValueRef;
+    use crate::Connection;
+
+    #[test]
+    fn test_preupdate_hook_insert() {
+        let db = Connection::open_in_memory().unwrap();
+
+        lazy_static! {
+            static ref CALLED: AtomicBool = AtomicBool::new(false);
+        }
+        db.preupdate_hook(Some(|action, db: &str, tbl: &str, case: &PreUpdateCase| {
+            assert_eq!(Action::SQLITE_INSERT, action);
+            assert_eq!("main", db);
+            assert_eq!("foo", tbl);
+            match case {
+                PreUpdateCase::Insert(accessor) => {
+                    assert_eq!(1, accessor.count());
+                    assert_eq!(0, accessor.depth());
+                    assert_eq!(1, accessor.new_rowid());
+                    assert_eq!(ValueRef::Text(b"lisa"), accessor.new_value(0).unwrap());
+                    assert!(accessor.new_value(1).is_err());
+                }
+                _ => panic!("wrong preupdate case"),
+            }
+            CALLED.store(true, Ordering::Relaxed);
+        }));
+        db.execute_batch("CREATE TABLE foo (t TEXT)").unwrap();
+        db.execute_batch("INSERT INTO foo VALUES ('lisa')").unwrap();
+        assert!(CALLED.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_preupdate_hook_delete() {
+        let db = Connection::open_in_memory().unwrap();
+
+        lazy_static! {
+            static ref CALLED: AtomicBool = AtomicBool::new(false);
+        }
+        db.execute_batch("CREATE TABLE foo (t TEXT)").unwrap();
+        db.execute_batch("INSERT INTO foo VALUES ('lisa')").unwrap();
+
+        db.preupdate_hook(Some(|action, db: &str, tbl: &str, case: &PreUpdateCase| {
+            assert_eq!(Action::SQLITE_DELETE, action);
+            assert_eq!("main", db);
+            assert_eq!("foo", tbl);
+            match case {
+                PreUpdateCase::Delete(accessor) => {
+                    assert_eq!(1, accessor.count());
+                    assert_eq!(1, accessor.old_rowid());
+                    assert_eq!(ValueRef::Text(b"lisa"), accessor.old_value(0).unwrap());
+                }
+                _ => panic!("wrong preupdate case"),
+            }
+            CALLED.store(true, Ordering::Relaxed);
+        }));
+        db.execute_batch("DELETE FROM foo").unwrap();
+        assert!(CALLED.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_preupdate_hook_update() {
+        let db = Connection::open_in_memory().unwrap();
+
+        lazy_static! {
+            static ref CALLED: AtomicBool = AtomicBool::new(false);
+        }
+        db.execute_batch("CREATE TABLE foo (t TEXT)").unwrap();
+        db.execute_batch("INSERT INTO foo VALUES ('lisa')").unwrap();
+
+        db.preupdate_hook(Some(|action, db: &str, tbl: &str, case: &PreUpdateCase| {
+            assert_eq!(Action::SQLITE_UPDATE, action);
+            assert_eq!("main", db);
+            assert_eq!("foo", tbl);
+            match case {
+                PreUpdateCase::Update {
+                    old_value_accessor,
+                    new_value_accessor,
+                } => {
+                    assert_eq!(1, old_value_accessor.count());
+                    assert_eq!(1, old_value_accessor.old_rowid());
+                    assert_eq!(
+                        ValueRef::Text(b"lisa"),
+                        old_value_accessor.old_value(0).unwrap()
+                    );
+                    assert_eq!(1, new_value_accessor.new_rowid());
+                    assert_eq!(
+                        ValueRef::Text(b"janice"),
+                        new_value_accessor.new_value(0).unwrap()
+                    );
+                }
+                _ => panic!("wrong preupdate case"),
+            }
+            CALLED.store(true, Ordering::Relaxed);
+        }));
+        db.execute_batch("UPDATE foo SET t = 'janice'").unwrap();
+        assert!(CALLED.load(Ordering::Relaxed));
+    }
+}
@@ -26,12 +26,30 @@ pub struct InnerConnection {
     // Otherwise, a long running query would prevent calling interrupt, as
     // interrupt would only acquire the lock after the query's completion.
     interrupt_lock: Arc<Mutex<*mut ffi::sqlite3>>,
+    // Kept alive as long as SQLite may invoke it, see `Connection::busy_handler`.
+    pub busy_handler: Option<Box<crate::busy::BoxedBusyHandler>>,
     #[cfg(feature = "hooks")]
     pub free_commit_hook: Option<fn(*mut ::std::os::raw::c_void)>,
     #[cfg(feature = "hooks")]
     pub free_rollback_hook: Option<fn(*mut ::std::os::raw::c_void)>,
     #[cfg(feature = "hooks")]
     pub free_update_hook: Option<fn(*mut ::std::os::raw::c_void)>,
+    #[cfg(feature = "hooks")]
+    pub free_wal_hook: Option<fn(*mut ::std::os::raw::c_void)>,
+    #[cfg(feature = "preupdate_hook")]
+    pub free_preupdate_hook: Option<fn(*mut ::std::os::raw::c_void)>,
+    #[cfg(feature = "hooks")]
+    pub progress_handler: Option<crate::hooks::ProgressHandler>,
+    // Set when the progress handler interrupts an operation, so that the
+    // resulting `SQLITE_INTERRUPT` can be reported as such.
+    #[cfg(feature = "hooks")]
+    pub progress_interrupted: Arc<AtomicBool>,
+    #[cfg(feature = "hooks")]
+    pub authorizer: Option<crate::hooks::BoxedAuthorizer>,
+    // Kept alive until the connection is closed, since the tracer is also
+    // invoked by `sqlite3_close`.
+    #[cfg(all(feature = "trace", feature = "modern_sqlite"))]
+    pub tracer: Option<Box<crate::trace::BoxedTracer>>,
     owned: bool,
 }
 
@@ -42,6 +60,9 @@ impl InnerConnection {
         InnerConnection {
             db,
             interrupt_lock: Arc::new(Mutex::new(db)),
+            busy_handler: None,
+            #[cfg(all(feature = "trace", feature = "modern_sqlite"))]
+            tracer: None,
             owned,
         }
     }
@@ -52,9 +73,18 @@ impl InnerConnection {
         InnerConnection {
             db,
             interrupt_lock: Arc::new(Mutex::new(db)),
+            busy_handler: None,
             free_commit_hook: None,
             free_rollback_hook: None,
             free_update_hook: None,
+            free_wal_hook: None,
+            #[cfg(feature = "preupdate_hook")]
+            free_preupdate_hook: None,
+            progress_handler: None,
+            progress_interrupted: Arc::new(AtomicBool::new(false)),
+            authorizer: None,
+            #[cfg(all(feature = "trace", feature = "modern_sqlite"))]
+            tracer: None,
             owned,
         }
     }
@@ -135,6 +165,14 @@ impl InnerConnection {
     }
 
     pub fn decode_result(&mut self, code: c_int) -> Result<()> {
+        #[cfg(feature = "hooks")]
+        {
+            if code & 0xff == ffi::SQLITE_INTERRUPT
+                && self.progress_interrupted.swap(false, Ordering::Relaxed)
+            {
+                return Err(Error::ProgressHandlerInterrupted);
+            }
+        }
         InnerConnection::decode_result_raw(self.db(), code)
     }
 
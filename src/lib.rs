@@ -80,23 +80,36 @@ pub use crate::column::Column;
 pub use crate::error::Error;
 pub use crate::ffi::ErrorCode;
 #[cfg(feature = "hooks")]
-pub use crate::hooks::Action;
+pub use crate::hooks::{
+    Action, AuthAction, AuthContext, Authorization, CheckpointMode, CheckpointStatus,
+    TransactionOperation,
+};
+#[cfg(feature = "preupdate_hook")]
+pub use crate::hooks::{PreUpdateCase, PreUpdateNewValueAccessor, PreUpdateOldValueAccessor};
 #[cfg(feature = "load_extension")]
 pub use crate::load_extension_guard::LoadExtensionGuard;
-pub use crate::row::{AndThenRows, MappedRows, Row, RowIndex, Rows};
-pub use crate::statement::{Statement, StatementStatus};
+pub use crate::pragma::{
+    AutoVacuum, ConnectionOptions, ForeignKeyViolation, IntegrityReport, JournalMode, LockingMode,
+    Synchronous, TempStore,
+};
+pub use crate::row::{AndThenRows, FromRow, MappedRows, Row, RowIndex, Rows};
+pub use crate::statement::{Statement, StatementStatus, ToParams};
 pub use crate::transaction::{DropBehavior, Savepoint, Transaction, TransactionBehavior};
 pub use crate::types::ToSql;
 pub use crate::version::*;
+#[cfg(feature = "derive")]
+pub use rusqlite_derive::{FromRow, ToParams};
 
 #[macro_use]
 mod error;
 
+#[cfg(feature = "async")]
+pub mod async_connection;
 #[cfg(feature = "backup")]
 pub mod backup;
 #[cfg(feature = "blob")]
 pub mod blob;
-mod busy;
+pub mod busy;
 mod cache;
 #[cfg(feature = "collation")]
 mod collation;
@@ -113,17 +126,29 @@ mod inner_connection;
 pub mod limits;
 #[cfg(feature = "load_extension")]
 mod load_extension_guard;
+#[cfg(feature = "migrations")]
+pub mod migrations;
+#[cfg(feature = "pool")]
+pub mod pool;
 mod pragma;
 mod raw_statement;
 mod row;
+#[cfg(feature = "schema")]
+pub mod schema;
+#[cfg(feature = "serde")]
+pub mod serde;
+#[cfg(feature = "serialize")]
+pub mod serialize;
 #[cfg(feature = "session")]
 pub mod session;
 mod statement;
+pub mod status;
 #[cfg(feature = "trace")]
 pub mod trace;
 mod transaction;
 pub mod types;
 mod unlock_notify;
+pub mod vacuum;
 mod version;
 #[cfg(feature = "vtab")]
 pub mod vtab;
@@ -283,13 +308,16 @@ pub enum DatabaseName<'a> {
     Attached(&'a str),
 }
 
-// Currently DatabaseName is only used by the backup and blob mods, so hide
-// this (private) impl to avoid dead code warnings.
+// Currently DatabaseName is only used by the backup, blob, hooks, session and
+// schema (with column_metadata) mods, so hide this (private) impl to avoid dead
+// code warnings.
 #[cfg(any(
     feature = "backup",
     feature = "blob",
+    feature = "hooks",
     feature = "session",
-    feature = "modern_sqlite"
+    feature = "modern_sqlite",
+    all(feature = "schema", feature = "column_metadata")
 ))]
 impl DatabaseName<'_> {
     fn to_cstring(&self) -> Result<CString> {
@@ -554,6 +582,47 @@ impl Connection {
         stmt.query_row(params, f)
     }
 
+    /// Convenience method to execute a query that is expected to return a
+    /// single row, converted to `T` with `FromRow`.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,no_run
+    /// # use rusqlite::{Connection, FromRow, Result, Row};
+    /// struct Person {
+    ///     name: String,
+    ///     age: u32,
+    /// }
+    ///
+    /// impl FromRow for Person {
+    ///     fn from_row(row: &Row<'_>) -> Result<Person> {
+    ///         Ok(Person {
+    ///             name: row.get("name")?,
+    ///             age: row.get("age")?,
+    ///         })
+    ///     }
+    /// }
+    ///
+    /// fn oldest(conn: &Connection) -> Result<Person> {
+    ///     conn.query_row_as("SELECT name, age FROM person ORDER BY age DESC", &[] as &[&str])
+    /// }
+    /// ```
+    ///
+    /// With the `derive` feature, `FromRow` can be derived instead.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `sql` cannot be converted to a C-compatible string
+    /// or if the underlying SQLite call fails.
+    pub fn query_row_as<T, P>(&self, sql: &str, params: P) -> Result<T>
+    where
+        T: FromRow,
+        P: IntoIterator,
+        P::Item: ToSql,
+    {
+        self.query_row(sql, params, T::from_row)
+    }
+
     /// Convenience method to execute a query with named parameter(s) that is
     /// expected to return a single row.
     ///
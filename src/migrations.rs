@@ -0,0 +1,506 @@
+//! Schema migrations.
+//!
+//! [`Migrations`](struct.Migrations.html) applies an ordered list of
+//! [`Migration`](struct.Migration.html)s, each with an up step and an
+//! optional down step. The schema version is the number of applied
+//! migrations; it is recorded in `PRAGMA user_version` or, with
+//! [`with_table`](struct.Migrations.html#method.with_table), in a table that
+//! also stores a checksum of each applied migration.
+//!
+//! ```rust,no_run
+//! # use rusqlite::migrations::{Migration, Migrations};
+//! # use rusqlite::{Connection, Result};
+//! fn open_db() -> Result<Connection> {
+//!     let migrations = Migrations::new(vec![
+//!         Migration::new("CREATE TABLE person (id INTEGER PRIMARY KEY, name TEXT NOT NULL);")
+//!             .down("DROP TABLE person;"),
+//!         Migration::new("ALTER TABLE person ADD COLUMN email TEXT;"),
+//!     ]);
+//!     let mut conn = Connection::open("people.db")?;
+//!     migrations.to_latest(&mut conn)?;
+//!     Ok(conn)
+//! }
+//! ```
+use std::borrow::Cow;
+use std::error;
+use std::fmt;
+
+use crate::pragma::Sql;
+use crate::{Connection, Error, Result, Transaction, TransactionBehavior, NO_PARAMS};
+
+/// Reasons why `Migrations` cannot migrate a database, reported as
+/// `Error::MigrationError`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MigrationError {
+    /// The schema version of the database is newer than the latest known
+    /// migration, i.e. the database was migrated by a newer version of the
+    /// code.
+    DatabaseTooNew {
+        /// Schema version of the database.
+        version: usize,
+        /// Version of the latest known migration.
+        latest: usize,
+    },
+    /// The schema version recorded in the database is negative (e.g.
+    /// `PRAGMA user_version` was set by another tool).
+    InvalidVersion {
+        /// Recorded schema version.
+        version: i64,
+    },
+    /// The checksum recorded for an applied migration does not match the
+    /// SQL of its up step anymore.
+    ChecksumMismatch {
+        /// Version of the modified migration.
+        version: usize,
+    },
+    /// The migration to revert has no down step.
+    Irreversible {
+        /// Version of the migration without down step.
+        version: usize,
+    },
+    /// The requested version is newer than the latest known migration.
+    UnknownVersion {
+        /// Requested version.
+        version: usize,
+        /// Version of the latest known migration.
+        latest: usize,
+    },
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            MigrationError::DatabaseTooNew { version, latest } => write!(
+                f,
+                "Database schema version {} is newer than the latest migration {}",
+                version, latest
+            ),
+            MigrationError::InvalidVersion { version } => {
+                write!(f, "Invalid database schema version {}", version)
+            }
+            MigrationError::ChecksumMismatch { version } => {
+                write!(f, "Migration {} was modified after being applied", version)
+            }
+            MigrationError::Irreversible { version } => {
+                write!(f, "Migration {} has no down step", version)
+            }
+            MigrationError::UnknownVersion { version, latest } => write!(
+                f,
+                "Unknown schema version {} (latest migration is {})",
+                version, latest
+            ),
+        }
+    }
+}
+
+impl error::Error for MigrationError {}
+
+impl From<MigrationError> for Error {
+    fn from(err: MigrationError) -> Error {
+        Error::MigrationError(err)
+    }
+}
+
+type StepFn = dyn Fn(&Transaction<'_>) -> Result<()> + Send + Sync + 'static;
+
+enum Step {
+    Sql(Cow<'static, str>),
+    Fn(Box<StepFn>),
+}
+
+impl Step {
+    fn run(&self, tx: &Transaction<'_>) -> Result<()> {
+        match *self {
+            Step::Sql(ref sql) => tx.execute_batch(sql),
+            Step::Fn(ref f) => f(tx),
+        }
+    }
+
+    /// 64-bit FNV-1a hash of the SQL, which (unlike `DefaultHasher`) is
+    /// stable across Rust versions.
+    fn checksum(&self) -> i64 {
+        let sql = match *self {
+            Step::Sql(ref sql) => sql.as_bytes(),
+            Step::Fn(_) => b"",
+        };
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for &b in sql {
+            hash ^= u64::from(b);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+        hash as i64
+    }
+}
+
+impl fmt::Debug for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Step::Sql(ref sql) => f.debug_tuple("Sql").field(sql).finish(),
+            Step::Fn(_) => f.debug_tuple("Fn").finish(),
+        }
+    }
+}
+
+/// A single migration: an up step, and an optional down step to revert it.
+#[derive(Debug)]
+pub struct Migration {
+    up: Step,
+    down: Option<Step>,
+}
+
+impl Migration {
+    /// A migration whose up step executes `sql` (which may contain several
+    /// statements).
+    pub fn new<S: Into<Cow<'static, str>>>(sql: S) -> Migration {
+        Migration {
+            up: Step::Sql(sql.into()),
+            down: None,
+        }
+    }
+
+    /// A migration whose up step runs `f`.
+    ///
+    /// Checksums only cover SQL steps: modifying `f` after the migration has
+    /// been applied is not detected.
+    pub fn from_fn<F>(f: F) -> Migration
+    where
+        F: Fn(&Transaction<'_>) -> Result<()> + Send + Sync + 'static,
+    {
+        Migration {
+            up: Step::Fn(Box::new(f)),
+            down: None,
+        }
+    }
+
+    /// Set a down step executing `sql`.
+    pub fn down<S: Into<Cow<'static, str>>>(mut self, sql: S) -> Migration {
+        self.down = Some(Step::Sql(sql.into()));
+        self
+    }
+
+    /// Set a down step running `f`.
+    pub fn down_fn<F>(mut self, f: F) -> Migration
+    where
+        F: Fn(&Transaction<'_>) -> Result<()> + Send + Sync + 'static,
+    {
+        self.down = Some(Step::Fn(Box::new(f)));
+        self
+    }
+}
+
+/// An ordered list of migrations.
+///
+/// Version `n` means that the first `n` migrations have been applied.
+#[derive(Debug)]
+pub struct Migrations {
+    migrations: Vec<Migration>,
+    table: Option<String>,
+}
+
+impl Migrations {
+    /// Migrations recording the schema version in `PRAGMA user_version`.
+    pub fn new(migrations: Vec<Migration>) -> Migrations {
+        Migrations {
+            migrations,
+            table: None,
+        }
+    }
+
+    /// Record applied migrations, with a checksum of their SQL, in `table`
+    /// instead of `PRAGMA user_version`. The table is created if needed.
+    pub fn with_table<S: Into<String>>(mut self, table: S) -> Migrations {
+        self.table = Some(table.into());
+        self
+    }
+
+    /// Version of the latest migration.
+    pub fn latest(&self) -> usize {
+        self.migrations.len()
+    }
+
+    /// Schema version of the database.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the version cannot be read, or an
+    /// `Error::MigrationError` if it is negative.
+    pub fn current_version(&self, conn: &Connection) -> Result<usize> {
+        match self.table {
+            None => conn
+                .pragma_query_value(None, "user_version", |r| r.get::<_, i64>(0))
+                .and_then(checked_version),
+            Some(ref table) => {
+                let exists: bool = conn.query_row(
+                    "SELECT count(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = ?",
+                    &[table],
+                    |r| r.get(0),
+                )?;
+                if !exists {
+                    return Ok(0);
+                }
+                conn.query_row(
+                    &format!("SELECT coalesce(max(version), 0) FROM {}", quote(table)),
+                    NO_PARAMS,
+                    |r| r.get::<_, i64>(0),
+                )
+                .and_then(checked_version)
+            }
+        }
+    }
+
+    /// Apply all pending migrations. See [`to_version`](#method.to_version).
+    pub fn to_latest(&self, conn: &mut Connection) -> Result<()> {
+        self.to_version(conn, self.latest())
+    }
+
+    /// Migrate the database up or down to `version`.
+    ///
+    /// All steps are run in a single (immediate) transaction: either the
+    /// database reaches `version`, or it is left unchanged.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if a step fails, or an `Error::MigrationError` if:
+    /// * the schema version of the database is negative,
+    /// * the database is newer than the latest migration,
+    /// * an applied migration was modified (when recorded in a table),
+    /// * a migration to revert has no down step,
+    /// * `version` is newer than the latest migration.
+    pub fn to_version(&self, conn: &mut Connection, version: usize) -> Result<()> {
+        let latest = self.latest();
+        if version > latest {
+            return Err(MigrationError::UnknownVersion { version, latest }.into());
+        }
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let current = self.check(&tx)?;
+        if version > current {
+            for (i, migration) in self.migrations[current..version].iter().enumerate() {
+                migration.up.run(&tx)?;
+                if let Some(ref table) = self.table {
+                    tx.execute(
+                        &format!(
+                            "INSERT INTO {} (version, checksum) VALUES (?, ?)",
+                            quote(table)
+                        ),
+                        [(current + i + 1) as i64, migration.up.checksum()],
+                    )?;
+                }
+            }
+        } else {
+            for v in (version..current).rev() {
+                let down = match self.migrations[v].down {
+                    Some(ref down) => down,
+                    None => return Err(MigrationError::Irreversible { version: v + 1 }.into()),
+                };
+                down.run(&tx)?;
+                if let Some(ref table) = self.table {
+                    tx.execute(
+                        &format!("DELETE FROM {} WHERE version = ?", quote(table)),
+                        [(v + 1) as i64],
+                    )?;
+                }
+            }
+        }
+        if self.table.is_none() {
+            tx.pragma_update(None, "user_version", &(version as i64))?;
+        }
+        tx.commit()
+    }
+
+    /// Return the current version, after checking that the database is not
+    /// newer than the code and that applied migrations are unchanged.
+    fn check(&self, tx: &Transaction<'_>) -> Result<usize> {
+        let latest = self.latest();
+        let table = match self.table {
+            None => {
+                let version = self.current_version(tx)?;
+                if version > latest {
+                    return Err(MigrationError::DatabaseTooNew { version, latest }.into());
+                }
+                return Ok(version);
+            }
+            Some(ref table) => quote(table),
+        };
+        tx.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                version INTEGER PRIMARY KEY,
+                checksum INTEGER NOT NULL,
+                applied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );",
+            table
+        ))?;
+        let mut stmt = tx.prepare(&format!(
+            "SELECT version, checksum FROM {} ORDER BY version",
+            table
+        ))?;
+        let mut rows = stmt.query(NO_PARAMS)?;
+        let mut current = 0;
+        while let Some(row) = rows.next()? {
+            let version = checked_version(row.get(0)?)?;
+            let checksum: i64 = row.get(1)?;
+            if version > latest {
+                return Err(MigrationError::DatabaseTooNew { version, latest }.into());
+            }
+            if version == 0 || checksum != self.migrations[version - 1].up.checksum() {
+                return Err(MigrationError::ChecksumMismatch { version }.into());
+            }
+            current = version;
+        }
+        Ok(current)
+    }
+}
+
+/// Reject the negative versions that `user_version`, a signed 32-bit integer,
+/// can hold.
+fn checked_version(version: i64) -> Result<usize> {
+    if version < 0 {
+        return Err(MigrationError::InvalidVersion { version }.into());
+    }
+    Ok(version as usize)
+}
+
+fn quote(table: &str) -> String {
+    let mut sql = Sql::new();
+    sql.push_identifier(table);
+    sql.as_str().to_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Migration, MigrationError, Migrations};
+    use crate::{Connection, Error, NO_PARAMS};
+
+    fn migrations() -> Migrations {
+        Migrations::new(vec![
+            Migration::new("CREATE TABLE foo(x INTEGER);").down("DROP TABLE foo;"),
+            Migration::from_fn(|tx| tx.execute_batch("INSERT INTO foo VALUES (42);"))
+                .down_fn(|tx| tx.execute_batch("DELETE FROM foo;")),
+            Migration::new("CREATE TABLE bar(y TEXT);"),
+        ])
+    }
+
+    fn count_foo(db: &Connection) -> i64 {
+        db.query_row("SELECT count(*) FROM foo", NO_PARAMS, |r| r.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_user_version() {
+        let mut db = Connection::open_in_memory().unwrap();
+        let migrations = migrations();
+        assert_eq!(0, migrations.current_version(&db).unwrap());
+
+        migrations.to_version(&mut db, 2).unwrap();
+        assert_eq!(2, migrations.current_version(&db).unwrap());
+        assert_eq!(1, count_foo(&db));
+
+        migrations.to_latest(&mut db).unwrap();
+        let user_version: i64 = db
+            .pragma_query_value(None, "user_version", |r| r.get(0))
+            .unwrap();
+        assert_eq!(3, user_version);
+        // no-op
+        migrations.to_latest(&mut db).unwrap();
+        assert_eq!(1, count_foo(&db));
+
+        // bar cannot be dropped
+        assert_eq!(
+            Error::MigrationError(MigrationError::Irreversible { version: 3 }),
+            migrations.to_version(&mut db, 0).unwrap_err()
+        );
+        assert_eq!(3, migrations.current_version(&db).unwrap());
+
+        migrations.to_version(&mut db, 4).unwrap_err();
+    }
+
+    #[test]
+    fn test_down() {
+        let mut db = Connection::open_in_memory().unwrap();
+        let migrations = migrations();
+        migrations.to_version(&mut db, 2).unwrap();
+        migrations.to_version(&mut db, 1).unwrap();
+        assert_eq!(0, count_foo(&db));
+        migrations.to_version(&mut db, 0).unwrap();
+        assert!(db.prepare("SELECT * FROM foo").is_err());
+        assert_eq!(0, migrations.current_version(&db).unwrap());
+    }
+
+    #[test]
+    fn test_atomic() {
+        let mut db = Connection::open_in_memory().unwrap();
+        let migrations = Migrations::new(vec![
+            Migration::new("CREATE TABLE foo(x INTEGER);"),
+            Migration::new("INSERT INTO missing VALUES (1);"),
+        ]);
+        migrations.to_latest(&mut db).unwrap_err();
+        assert_eq!(0, migrations.current_version(&db).unwrap());
+        assert!(db.prepare("SELECT * FROM foo").is_err());
+    }
+
+    #[test]
+    fn test_database_too_new() {
+        let mut db = Connection::open_in_memory().unwrap();
+        migrations().to_latest(&mut db).unwrap();
+        let old = Migrations::new(vec![Migration::new("CREATE TABLE foo(x INTEGER);")]);
+        assert_eq!(
+            Error::MigrationError(MigrationError::DatabaseTooNew {
+                version: 3,
+                latest: 1
+            }),
+            old.to_latest(&mut db).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_negative_version() {
+        let mut db = Connection::open_in_memory().unwrap();
+        db.pragma_update(None, "user_version", &-1).unwrap();
+        let err = Error::MigrationError(MigrationError::InvalidVersion { version: -1 });
+        assert_eq!(err, migrations().current_version(&db).unwrap_err());
+        assert_eq!(err, migrations().to_latest(&mut db).unwrap_err());
+    }
+
+    #[test]
+    fn test_table() {
+        let mut db = Connection::open_in_memory().unwrap();
+        let migrations = migrations().with_table("schema migrations");
+        assert_eq!(0, migrations.current_version(&db).unwrap());
+        migrations.to_latest(&mut db).unwrap();
+        assert_eq!(3, migrations.current_version(&db).unwrap());
+        let user_version: i64 = db
+            .pragma_query_value(None, "user_version", |r| r.get(0))
+            .unwrap();
+        assert_eq!(0, user_version);
+
+        migrations.to_version(&mut db, 2).unwrap_err();
+        let applied: i64 = db
+            .query_row(
+                "SELECT count(*) FROM \"schema migrations\"",
+                NO_PARAMS,
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(3, applied);
+
+        let modified = Migrations::new(vec![
+            Migration::new("CREATE TABLE foo(x INTEGER, y INTEGER);"),
+            Migration::new(""),
+            Migration::new("CREATE TABLE bar(y TEXT);"),
+        ])
+        .with_table("schema migrations");
+        assert_eq!(
+            Error::MigrationError(MigrationError::ChecksumMismatch { version: 1 }),
+            modified.to_latest(&mut db).unwrap_err()
+        );
+
+        let old = Migrations::new(vec![Migration::new("CREATE TABLE foo(x INTEGER);")])
+            .with_table("schema migrations");
+        assert_eq!(
+            Error::MigrationError(MigrationError::DatabaseTooNew {
+                version: 2,
+                latest: 1
+            }),
+            old.to_latest(&mut db).unwrap_err()
+        );
+    }
+}
@@ -0,0 +1,658 @@
+//! Pool of connections to the same database.
+//!
+//! ```rust,no_run
+//! # use rusqlite::pool::Pool;
+//! # use rusqlite::{Result, NO_PARAMS};
+//! # use std::time::Duration;
+//! fn count_people() -> Result<i64> {
+//!     let pool = Pool::builder("people.db")
+//!         .size(4)
+//!         .init(|conn| conn.busy_timeout(Duration::from_secs(5)))
+//!         .build()?;
+//!     let conn = pool.get()?;
+//!     conn.query_row("SELECT count(*) FROM people", NO_PARAMS, |r| r.get(0))
+//! }
+//! ```
+//!
+//! [`PoolBuilder::build_read_write`](struct.PoolBuilder.html#method.build_read_write)
+//! creates a pool of read-only connections plus a single writer, for
+//! databases in WAL mode.
+use std::fmt;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::ffi;
+use crate::{Connection, Error, OpenFlags, Result, NO_PARAMS};
+
+type InitFn = dyn Fn(&mut Connection) -> Result<()> + Send + Sync + 'static;
+
+/// When the prepared statement cache of a pooled connection is flushed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlushPolicy {
+    /// Keep cached statements for the lifetime of the connection.
+    Never,
+    /// Flush the cache each time the connection is returned to the pool.
+    OnReturn,
+}
+
+// `#[default]` on enum variants requires Rust 1.62.
+#[allow(clippy::derivable_impls)]
+impl Default for FlushPolicy {
+    fn default() -> FlushPolicy {
+        FlushPolicy::Never
+    }
+}
+
+/// Configuration of a [`Pool`](struct.Pool.html), created by
+/// [`Pool::builder`](struct.Pool.html#method.builder).
+pub struct PoolBuilder {
+    path: PathBuf,
+    size: usize,
+    flags: OpenFlags,
+    init: Option<Arc<InitFn>>,
+    flush_policy: FlushPolicy,
+    timeout: Option<Duration>,
+}
+
+impl PoolBuilder {
+    /// Set the maximum number of connections (4 by default).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is 0.
+    pub fn size(mut self, size: usize) -> PoolBuilder {
+        assert!(size > 0, "pool size must be greater than 0");
+        self.size = size;
+        self
+    }
+
+    /// Set the flags used to open connections (`OpenFlags::default()` by
+    /// default).
+    pub fn flags(mut self, flags: OpenFlags) -> PoolBuilder {
+        self.flags = flags;
+        self
+    }
+
+    /// Set a closure run on each new connection, before it is handed out
+    /// for the first time (to set pragmas, register functions, ...).
+    pub fn init<F>(mut self, init: F) -> PoolBuilder
+    where
+        F: Fn(&mut Connection) -> Result<()> + Send + Sync + 'static,
+    {
+        self.init = Some(Arc::new(init));
+        self
+    }
+
+    /// Set when the prepared statement cache of connections is flushed.
+    pub fn flush_policy(mut self, flush_policy: FlushPolicy) -> PoolBuilder {
+        self.flush_policy = flush_policy;
+        self
+    }
+
+    /// Set how long `Pool::get` waits for a connection to be returned when
+    /// all of them are in use. By default, it waits forever.
+    pub fn timeout(mut self, timeout: Duration) -> PoolBuilder {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Open the connections of the pool.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if a connection cannot be opened or if the init
+    /// closure fails.
+    pub fn build(self) -> Result<Pool> {
+        let pool = Pool {
+            shared: Arc::new(Shared {
+                path: self.path,
+                size: self.size,
+                flags: self.flags,
+                init: self.init,
+                flush_policy: self.flush_policy,
+                timeout: self.timeout,
+                state: Mutex::new(State {
+                    idle: Vec::with_capacity(self.size),
+                    open: 0,
+                }),
+                available: Condvar::new(),
+            }),
+        };
+        let mut conns = Vec::with_capacity(pool.shared.size);
+        for _ in 0..pool.shared.size {
+            conns.push(pool.shared.open()?);
+        }
+        let mut state = pool.shared.state.lock().unwrap();
+        state.open = conns.len();
+        state.idle = conns;
+        drop(state);
+        Ok(pool)
+    }
+
+    /// Open a single writer connection, switched to WAL mode, and a pool of
+    /// `size` read-only connections.
+    ///
+    /// The writer is opened with the configured flags, with
+    /// `SQLITE_OPEN_READ_ONLY` replaced by `SQLITE_OPEN_READ_WRITE`; readers
+    /// with `SQLITE_OPEN_READ_WRITE` and `SQLITE_OPEN_CREATE` replaced by
+    /// `SQLITE_OPEN_READ_ONLY`. The init closure is run on both.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if a connection cannot be opened, if the database
+    /// cannot be switched to WAL mode or if the init closure fails.
+    pub fn build_read_write(self) -> Result<ReadWritePool> {
+        let writer_flags =
+            (self.flags - OpenFlags::SQLITE_OPEN_READ_ONLY) | OpenFlags::SQLITE_OPEN_READ_WRITE;
+        let reader_flags =
+            (self.flags - OpenFlags::SQLITE_OPEN_READ_WRITE - OpenFlags::SQLITE_OPEN_CREATE)
+                | OpenFlags::SQLITE_OPEN_READ_ONLY;
+        let user_init = self.init.clone();
+        let writer = PoolBuilder {
+            path: self.path.clone(),
+            size: 1,
+            flags: writer_flags,
+            init: Some(Arc::new(move |conn: &mut Connection| {
+                let mode: String =
+                    conn.query_row("PRAGMA journal_mode = WAL", NO_PARAMS, |r| r.get(0))?;
+                if !mode.eq_ignore_ascii_case("wal") {
+                    return Err(Error::SqliteFailure(
+                        ffi::Error::new(ffi::SQLITE_ERROR),
+                        Some(format!("cannot switch to WAL mode: {}", mode)),
+                    ));
+                }
+                match user_init {
+                    Some(ref init) => init(conn),
+                    None => Ok(()),
+                }
+            })),
+            flush_policy: self.flush_policy,
+            timeout: self.timeout,
+        }
+        .build()?;
+        let readers = self.flags(reader_flags).build()?;
+        Ok(ReadWritePool { readers, writer })
+    }
+}
+
+impl fmt::Debug for PoolBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PoolBuilder")
+            .field("path", &self.path)
+            .field("size", &self.size)
+            .field("flags", &self.flags)
+            .field("flush_policy", &self.flush_policy)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+enum Take {
+    Idle(Box<Connection>),
+    // `State::open` has been incremented for a connection yet to be opened
+    Reserved,
+}
+
+struct State {
+    idle: Vec<Connection>,
+    // idle and handed out connections
+    open: usize,
+}
+
+struct Shared {
+    path: PathBuf,
+    size: usize,
+    flags: OpenFlags,
+    init: Option<Arc<InitFn>>,
+    flush_policy: FlushPolicy,
+    timeout: Option<Duration>,
+    state: Mutex<State>,
+    available: Condvar,
+}
+
+impl Shared {
+    fn open(&self) -> Result<Connection> {
+        let mut conn = Connection::open_with_flags(&self.path, self.flags)?;
+        if let Some(ref init) = self.init {
+            init(&mut conn)?;
+        }
+        Ok(conn)
+    }
+
+    /// Open a connection in a slot reserved by `Pool::try_take`. The slot is
+    /// released if the connection cannot be opened, or if `init` panics.
+    fn open_reserved(&self) -> Result<Connection> {
+        struct Reservation<'a>(&'a Shared);
+
+        impl Drop for Reservation<'_> {
+            fn drop(&mut self) {
+                self.0.state.lock().unwrap().open -= 1;
+                self.0.available.notify_one();
+            }
+        }
+
+        let reservation = Reservation(self);
+        let conn = self.open()?;
+        mem::forget(reservation);
+        Ok(conn)
+    }
+
+    fn put(&self, conn: Connection) {
+        let mut state = self.state.lock().unwrap();
+        if conn.is_autocommit() {
+            if self.flush_policy == FlushPolicy::OnReturn {
+                conn.flush_prepared_statement_cache();
+            }
+            state.idle.push(conn);
+        } else {
+            // A transaction was left open: close the connection (which rolls
+            // the transaction back) and open a new one on demand.
+            state.open -= 1;
+            drop(state);
+            drop(conn);
+        }
+        self.available.notify_one();
+    }
+}
+
+/// A pool of connections to the same database.
+///
+/// Connections are opened by [`Pool::builder`](#method.builder) and handed
+/// out by [`get`](#method.get) as a [`PooledConnection`](struct.PooledConnection.html),
+/// which returns the connection to the pool when dropped. Connections
+/// returned with an open transaction are closed and replaced by new ones.
+///
+/// Cloning a `Pool` is cheap: clones share the same connections.
+#[derive(Clone)]
+pub struct Pool {
+    shared: Arc<Shared>,
+}
+
+impl Pool {
+    /// Start the configuration of a pool of connections to the database at
+    /// `path`.
+    ///
+    /// Note that each connection to `":memory:"` opens a different in-memory
+    /// database; use a URI like `file:name?mode=memory&cache=shared` to share
+    /// one.
+    pub fn builder<P: AsRef<Path>>(path: P) -> PoolBuilder {
+        PoolBuilder {
+            path: path.as_ref().to_path_buf(),
+            size: 4,
+            flags: OpenFlags::default(),
+            init: None,
+            flush_policy: FlushPolicy::default(),
+            timeout: None,
+        }
+    }
+
+    /// Get a connection, waiting for one to be returned if all of them are
+    /// in use.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if no connection is returned within the configured
+    /// timeout, or if a replacement connection cannot be opened.
+    pub fn get(&self) -> Result<PooledConnection> {
+        let deadline = self.shared.timeout.map(|timeout| Instant::now() + timeout);
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if let Some(take) = self.try_take(&mut state) {
+                drop(state);
+                return self.finish_take(take);
+            }
+            state = match deadline {
+                None => self.shared.available.wait(state).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(Error::SqliteFailure(
+                            ffi::Error::new(ffi::SQLITE_BUSY),
+                            Some("timed out waiting for a pooled connection".to_owned()),
+                        ));
+                    }
+                    self.shared
+                        .available
+                        .wait_timeout(state, deadline - now)
+                        .unwrap()
+                        .0
+                }
+            };
+        }
+    }
+
+    /// Get a connection if one is available, without waiting.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if a replacement connection cannot be opened.
+    pub fn try_get(&self) -> Result<Option<PooledConnection>> {
+        let mut state = self.shared.state.lock().unwrap();
+        let take = self.try_take(&mut state);
+        drop(state);
+        take.map(|take| self.finish_take(take)).transpose()
+    }
+
+    fn try_take(&self, state: &mut State) -> Option<Take> {
+        if let Some(conn) = state.idle.pop() {
+            return Some(Take::Idle(Box::new(conn)));
+        }
+        if state.open < self.shared.size {
+            // reserve a slot to replace a connection closed by `Shared::put`
+            state.open += 1;
+            return Some(Take::Reserved);
+        }
+        None
+    }
+
+    /// Must be called without holding the lock, so that other threads are
+    /// not blocked while a connection is opened.
+    fn finish_take(&self, take: Take) -> Result<PooledConnection> {
+        match take {
+            Take::Idle(conn) => Ok(self.guard(*conn)),
+            Take::Reserved => self.shared.open_reserved().map(|conn| self.guard(conn)),
+        }
+    }
+
+    fn guard(&self, conn: Connection) -> PooledConnection {
+        PooledConnection {
+            conn: Some(conn),
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Maximum number of connections.
+    pub fn size(&self) -> usize {
+        self.shared.size
+    }
+
+    /// Number of connections currently available.
+    pub fn idle(&self) -> usize {
+        self.shared.state.lock().unwrap().idle.len()
+    }
+}
+
+impl fmt::Debug for Pool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pool")
+            .field("path", &self.shared.path)
+            .field("size", &self.shared.size)
+            .field("idle", &self.idle())
+            .finish()
+    }
+}
+
+/// A connection borrowed from a [`Pool`](struct.Pool.html), returned to it
+/// when dropped.
+pub struct PooledConnection {
+    conn: Option<Connection>,
+    shared: Arc<Shared>,
+}
+
+impl Deref for PooledConnection {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledConnection {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.conn.as_mut().unwrap()
+    }
+}
+
+impl fmt::Debug for PooledConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PooledConnection").field(&**self).finish()
+    }
+}
+
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            self.shared.put(conn);
+        }
+    }
+}
+
+/// A pool of read-only connections plus a single writer connection, created
+/// by [`PoolBuilder::build_read_write`](struct.PoolBuilder.html#method.build_read_write).
+#[derive(Clone, Debug)]
+pub struct ReadWritePool {
+    readers: Pool,
+    writer: Pool,
+}
+
+impl ReadWritePool {
+    /// Get a read-only connection. See [`Pool::get`](struct.Pool.html#method.get).
+    pub fn reader(&self) -> Result<PooledConnection> {
+        self.readers.get()
+    }
+
+    /// Get the writer connection, waiting for it to be returned if it is in
+    /// use. See [`Pool::get`](struct.Pool.html#method.get).
+    pub fn writer(&self) -> Result<PooledConnection> {
+        self.writer.get()
+    }
+
+    /// The pool of read-only connections.
+    pub fn readers(&self) -> &Pool {
+        &self.readers
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::os::raw::c_int;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::ptr;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use tempfile;
+
+    use super::{FlushPolicy, Pool};
+    use crate::ffi;
+    use crate::{Connection, Error, ErrorCode, NO_PARAMS};
+
+    fn error_code(err: Error) -> ErrorCode {
+        match err {
+            Error::SqliteFailure(err, _) => err.code,
+            err => panic!("unexpected error {:?}", err),
+        }
+    }
+
+    fn prepared_statements(conn: &Connection) -> usize {
+        let db = conn.db.borrow();
+        let mut n = 0;
+        let mut stmt = ptr::null_mut();
+        loop {
+            stmt = unsafe { ffi::sqlite3_next_stmt(db.db(), stmt) };
+            if stmt.is_null() {
+                return n;
+            }
+            n += 1;
+        }
+    }
+
+    #[test]
+    fn test_get_and_init() {
+        let dir = tempfile::tempdir().unwrap();
+        let opened = Arc::new(AtomicUsize::new(0));
+        let counter = opened.clone();
+        let pool = Pool::builder(dir.path().join("pool.db3"))
+            .size(2)
+            .init(move |conn| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                conn.execute_batch("PRAGMA foreign_keys = ON")
+            })
+            .build()
+            .unwrap();
+        assert_eq!(2, opened.load(Ordering::SeqCst));
+        assert_eq!(2, pool.idle());
+
+        {
+            let c1 = pool.get().unwrap();
+            let fk: c_int = c1
+                .query_row("PRAGMA foreign_keys", NO_PARAMS, |r| r.get(0))
+                .unwrap();
+            assert_eq!(1, fk);
+            let _c2 = pool.get().unwrap();
+            assert_eq!(0, pool.idle());
+            assert!(pool.try_get().unwrap().is_none());
+        }
+        assert_eq!(2, pool.idle());
+        assert_eq!(2, opened.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_timeout() {
+        let pool = Pool::builder(":memory:")
+            .size(1)
+            .timeout(Duration::from_millis(10))
+            .build()
+            .unwrap();
+        let _conn = pool.get().unwrap();
+        let err = pool.get().unwrap_err();
+        assert_eq!(ErrorCode::DatabaseBusy, error_code(err));
+    }
+
+    #[test]
+    fn test_open_transaction_discards_connection() {
+        let opened = Arc::new(AtomicUsize::new(0));
+        let counter = opened.clone();
+        let pool = Pool::builder(":memory:")
+            .size(1)
+            .init(move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            })
+            .build()
+            .unwrap();
+        {
+            let conn = pool.get().unwrap();
+            conn.execute_batch("CREATE TABLE foo(x); BEGIN; INSERT INTO foo VALUES (1);")
+                .unwrap();
+        }
+        assert_eq!(0, pool.idle());
+        let conn = pool.get().unwrap();
+        assert!(conn.is_autocommit());
+        assert_eq!(2, opened.load(Ordering::SeqCst));
+        // a new in-memory database
+        assert!(conn.prepare("SELECT * FROM foo").is_err());
+    }
+
+    #[test]
+    fn test_init_panic() {
+        let fail = Arc::new(AtomicBool::new(false));
+        let flag = fail.clone();
+        let pool = Pool::builder(":memory:")
+            .size(1)
+            .init(move |_| {
+                if flag.load(Ordering::SeqCst) {
+                    panic!("init failed");
+                }
+                Ok(())
+            })
+            .build()
+            .unwrap();
+        {
+            let conn = pool.get().unwrap();
+            conn.execute_batch("BEGIN").unwrap();
+        }
+        fail.store(true, Ordering::SeqCst);
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| pool.get())).is_err());
+
+        // neither poisoned nor leaking the reserved slot
+        fail.store(false, Ordering::SeqCst);
+        assert_eq!(0, pool.idle());
+        let conn = pool.get().unwrap();
+        assert!(conn.is_autocommit());
+    }
+
+    #[test]
+    fn test_flush_policy() {
+        for &(policy, expected) in &[(FlushPolicy::Never, 1), (FlushPolicy::OnReturn, 0)] {
+            let pool = Pool::builder(":memory:")
+                .size(1)
+                .flush_policy(policy)
+                .build()
+                .unwrap();
+            {
+                let conn = pool.get().unwrap();
+                conn.prepare_cached("SELECT 1").unwrap();
+            }
+            let conn = pool.get().unwrap();
+            assert_eq!(expected, prepared_statements(&conn));
+        }
+    }
+
+    #[test]
+    fn test_threads() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = Pool::builder(dir.path().join("threads.db3"))
+            .size(2)
+            .init(|conn| conn.busy_timeout(Duration::from_secs(5)))
+            .build()
+            .unwrap();
+        pool.get()
+            .unwrap()
+            .execute_batch("CREATE TABLE foo(x INTEGER)")
+            .unwrap();
+
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let pool = pool.clone();
+                thread::spawn(move || {
+                    let conn = pool.get().unwrap();
+                    conn.execute("INSERT INTO foo VALUES (?)", [i]).unwrap();
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        let count: i64 = pool
+            .get()
+            .unwrap()
+            .query_row("SELECT count(*) FROM foo", NO_PARAMS, |r| r.get(0))
+            .unwrap();
+        assert_eq!(4, count);
+    }
+
+    #[test]
+    fn test_read_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = Pool::builder(dir.path().join("wal.db3"))
+            .size(2)
+            .build_read_write()
+            .unwrap();
+        assert_eq!(2, pool.readers().size());
+
+        let writer = pool.writer().unwrap();
+        writer
+            .execute_batch("CREATE TABLE foo(x INTEGER); INSERT INTO foo VALUES (42);")
+            .unwrap();
+        let mode: String = writer
+            .query_row("PRAGMA journal_mode", NO_PARAMS, |r| r.get(0))
+            .unwrap();
+        assert_eq!("wal", mode);
+
+        let reader = pool.reader().unwrap();
+        let x: i64 = reader
+            .query_row("SELECT x FROM foo", NO_PARAMS, |r| r.get(0))
+            .unwrap();
+        assert_eq!(42, x);
+        let err = reader
+            .execute("INSERT INTO foo VALUES (1)", NO_PARAMS)
+            .unwrap_err();
+        assert_eq!(ErrorCode::ReadOnly, error_code(err));
+    }
+}
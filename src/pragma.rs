@@ -1,11 +1,13 @@
 //! Pragma helpers
 
 use std::ops::Deref;
+use std::path::Path;
+use std::time::Duration;
 
 use crate::error::Error;
 use crate::ffi;
-use crate::types::{ToSql, ToSqlOutput, ValueRef};
-use crate::{Connection, DatabaseName, Result, Row, NO_PARAMS};
+use crate::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use crate::{Connection, DatabaseName, OpenFlags, Result, Row, NO_PARAMS};
 
 pub struct Sql {
     buf: String,
@@ -283,6 +285,571 @@ impl Connection {
     }
 }
 
+/// Journal mode of a database, see
+/// [`PRAGMA journal_mode`](https://sqlite.org/pragma.html#pragma_journal_mode).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JournalMode {
+    /// The rollback journal is deleted at the end of each transaction.
+    Delete,
+    /// The rollback journal is truncated to zero length instead of deleted.
+    Truncate,
+    /// The rollback journal header is overwritten with zeros instead of
+    /// deleting the journal.
+    Persist,
+    /// The rollback journal is stored in volatile RAM.
+    Memory,
+    /// A write-ahead log is used instead of a rollback journal.
+    Wal,
+    /// The rollback journal is disabled.
+    Off,
+}
+
+impl JournalMode {
+    /// Returns the name SQLite uses for this mode.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JournalMode::Delete => "DELETE",
+            JournalMode::Truncate => "TRUNCATE",
+            JournalMode::Persist => "PERSIST",
+            JournalMode::Memory => "MEMORY",
+            JournalMode::Wal => "WAL",
+            JournalMode::Off => "OFF",
+        }
+    }
+
+    fn from_name(s: &str) -> Option<JournalMode> {
+        [
+            JournalMode::Delete,
+            JournalMode::Truncate,
+            JournalMode::Persist,
+            JournalMode::Memory,
+            JournalMode::Wal,
+            JournalMode::Off,
+        ]
+        .iter()
+        .copied()
+        .find(|m| m.as_str().eq_ignore_ascii_case(s))
+    }
+}
+
+/// Locking mode of a database, see
+/// [`PRAGMA locking_mode`](https://sqlite.org/pragma.html#pragma_locking_mode).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockingMode {
+    /// Locks are released at the end of each read or write transaction.
+    Normal,
+    /// Locks are never released once acquired.
+    Exclusive,
+}
+
+impl LockingMode {
+    /// Returns the name SQLite uses for this mode.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LockingMode::Normal => "NORMAL",
+            LockingMode::Exclusive => "EXCLUSIVE",
+        }
+    }
+
+    fn from_name(s: &str) -> Option<LockingMode> {
+        [LockingMode::Normal, LockingMode::Exclusive]
+            .iter()
+            .copied()
+            .find(|m| m.as_str().eq_ignore_ascii_case(s))
+    }
+}
+
+/// Synchronization level, see
+/// [`PRAGMA synchronous`](https://sqlite.org/pragma.html#pragma_synchronous).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Synchronous {
+    /// SQLite hands data off to the operating system without syncing.
+    Off = 0,
+    /// SQLite syncs at the most critical moments only.
+    Normal = 1,
+    /// SQLite syncs to ensure that an operating system crash or power failure
+    /// cannot corrupt the database.
+    Full = 2,
+    /// Like `Full`, with the directory containing a rollback journal also
+    /// synced after the journal is unlinked.
+    Extra = 3,
+}
+
+/// Location of temporary tables and indices, see
+/// [`PRAGMA temp_store`](https://sqlite.org/pragma.html#pragma_temp_store).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TempStore {
+    /// The compile-time default (`SQLITE_TEMP_STORE`) is used.
+    Default = 0,
+    /// Temporary tables and indices are stored in a file.
+    File = 1,
+    /// Temporary tables and indices are kept in memory.
+    Memory = 2,
+}
+
+/// Auto-vacuum mode of a database, see
+/// [`PRAGMA auto_vacuum`](https://sqlite.org/pragma.html#pragma_auto_vacuum).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AutoVacuum {
+    /// Freed pages are kept on the freelist until `VACUUM` is run.
+    None = 0,
+    /// Freelist pages are moved to the end of the database and truncated at
+    /// every commit.
+    Full = 1,
+    /// Freelist pages are only reclaimed by `PRAGMA incremental_vacuum`.
+    Incremental = 2,
+}
+
+macro_rules! text_pragma_enum {
+    ($t:ident) => {
+        impl ToSql for $t {
+            fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+                Ok(ToSqlOutput::from(self.as_str()))
+            }
+        }
+
+        impl FromSql for $t {
+            fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+                let s = value.as_str()?;
+                $t::from_name(s).ok_or_else(|| {
+                    FromSqlError::Other(format!("unknown {} \"{}\"", stringify!($t), s).into())
+                })
+            }
+        }
+    };
+}
+
+text_pragma_enum!(JournalMode);
+text_pragma_enum!(LockingMode);
+
+macro_rules! integer_pragma_enum {
+    ($t:ident, $($v:ident),+) => {
+        impl ToSql for $t {
+            fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+                Ok(ToSqlOutput::from(*self as i64))
+            }
+        }
+
+        impl FromSql for $t {
+            fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+                let i = value.as_i64()?;
+                $(if i == $t::$v as i64 {
+                    return Ok($t::$v);
+                })+
+                Err(FromSqlError::OutOfRange(i))
+            }
+        }
+    };
+}
+
+integer_pragma_enum!(Synchronous, Off, Normal, Full, Extra);
+integer_pragma_enum!(TempStore, Default, File, Memory);
+integer_pragma_enum!(AutoVacuum, None, Full, Incremental);
+
+/// Typed accessors for common pragmas.
+///
+/// These all apply to the `main` database.
+impl Connection {
+    /// Query the journal mode.
+    pub fn journal_mode(&self) -> Result<JournalMode> {
+        self.pragma_query_value(None, "journal_mode", |row| row.get(0))
+    }
+
+    /// Change the journal mode and return the mode actually in effect.
+    ///
+    /// SQLite silently keeps the current mode when the requested one is not
+    /// supported, e.g. `Wal` for an in-memory database.
+    pub fn set_journal_mode(&self, mode: JournalMode) -> Result<JournalMode> {
+        self.pragma_update_and_check(None, "journal_mode", &mode, |row| row.get(0))
+    }
+
+    /// Query the synchronization level.
+    pub fn synchronous(&self) -> Result<Synchronous> {
+        self.pragma_query_value(None, "synchronous", |row| row.get(0))
+    }
+
+    /// Change the synchronization level.
+    pub fn set_synchronous(&self, synchronous: Synchronous) -> Result<()> {
+        self.pragma_update(None, "synchronous", &synchronous)
+    }
+
+    /// Query whether foreign key constraints are enforced.
+    pub fn foreign_keys_enabled(&self) -> Result<bool> {
+        self.pragma_query_value(None, "foreign_keys", |row| row.get(0))
+    }
+
+    /// Enable or disable foreign key constraints enforcement.
+    ///
+    /// This is a no-op inside a transaction.
+    pub fn set_foreign_keys_enabled(&self, enabled: bool) -> Result<()> {
+        self.pragma_update(None, "foreign_keys", &enabled)
+    }
+
+    /// Query the suggested maximum number of pages held in memory.
+    ///
+    /// A negative value is a limit in kibibytes instead.
+    pub fn cache_size(&self) -> Result<i64> {
+        self.pragma_query_value(None, "cache_size", |row| row.get(0))
+    }
+
+    /// Change the suggested maximum number of pages held in memory.
+    ///
+    /// A negative value is a limit in kibibytes instead.
+    pub fn set_cache_size(&self, size: i64) -> Result<()> {
+        self.pragma_update(None, "cache_size", &size)
+    }
+
+    /// Query the page size in bytes.
+    pub fn page_size(&self) -> Result<u32> {
+        self.pragma_query_value(None, "page_size", |row| row.get(0))
+    }
+
+    /// Change the page size in bytes.
+    ///
+    /// Only takes effect before the database is created, or on the next
+    /// `VACUUM` when not in WAL mode.
+    pub fn set_page_size(&self, size: u32) -> Result<()> {
+        self.pragma_update(None, "page_size", &size)
+    }
+
+    /// Query the maximum number of bytes used for memory-mapped I/O.
+    pub fn mmap_size(&self) -> Result<i64> {
+        self.pragma_query_value(None, "mmap_size", |row| row.get(0))
+    }
+
+    /// Change the maximum number of bytes used for memory-mapped I/O.
+    pub fn set_mmap_size(&self, size: i64) -> Result<()> {
+        self.pragma_update(None, "mmap_size", &size)
+    }
+
+    /// Query where temporary tables and indices are stored.
+    pub fn temp_store(&self) -> Result<TempStore> {
+        self.pragma_query_value(None, "temp_store", |row| row.get(0))
+    }
+
+    /// Change where temporary tables and indices are stored.
+    pub fn set_temp_store(&self, temp_store: TempStore) -> Result<()> {
+        self.pragma_update(None, "temp_store", &temp_store)
+    }
+
+    /// Query the auto-vacuum mode.
+    pub fn auto_vacuum(&self) -> Result<AutoVacuum> {
+        self.pragma_query_value(None, "auto_vacuum", |row| row.get(0))
+    }
+
+    /// Change the auto-vacuum mode.
+    ///
+    /// Switching between `None` and another mode only takes effect before
+    /// the first table is created, or on the next `VACUUM`.
+    pub fn set_auto_vacuum(&self, auto_vacuum: AutoVacuum) -> Result<()> {
+        self.pragma_update(None, "auto_vacuum", &auto_vacuum)
+    }
+
+    /// Query the locking mode.
+    pub fn locking_mode(&self) -> Result<LockingMode> {
+        self.pragma_query_value(None, "locking_mode", |row| row.get(0))
+    }
+
+    /// Change the locking mode and return the mode now in effect.
+    pub fn set_locking_mode(&self, mode: LockingMode) -> Result<LockingMode> {
+        self.pragma_update_and_check(None, "locking_mode", &mode, |row| row.get(0))
+    }
+
+    /// Query the application ID stored in the database header.
+    pub fn application_id(&self) -> Result<i32> {
+        self.pragma_query_value(None, "application_id", |row| row.get(0))
+    }
+
+    /// Change the application ID stored in the database header.
+    pub fn set_application_id(&self, id: i32) -> Result<()> {
+        self.pragma_update(None, "application_id", &id)
+    }
+}
+
+/// Options applied to a connection when it is opened.
+///
+/// ```rust,no_run
+/// # use rusqlite::{ConnectionOptions, JournalMode, Result, Synchronous};
+/// # fn open() -> Result<()> {
+/// let conn = ConnectionOptions::new()
+///     .journal_mode(JournalMode::Wal)
+///     .synchronous(Synchronous::Normal)
+///     .foreign_keys(true)
+///     .open("app.db")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ConnectionOptions {
+    flags: OpenFlags,
+    busy_timeout: Option<Duration>,
+    page_size: Option<u32>,
+    auto_vacuum: Option<AutoVacuum>,
+    locking_mode: Option<LockingMode>,
+    journal_mode: Option<JournalMode>,
+    synchronous: Option<Synchronous>,
+    foreign_keys: Option<bool>,
+    cache_size: Option<i64>,
+    mmap_size: Option<i64>,
+    temp_store: Option<TempStore>,
+    application_id: Option<i32>,
+}
+
+impl ConnectionOptions {
+    /// Options which open a connection with the default flags and leave
+    /// every pragma untouched.
+    pub fn new() -> ConnectionOptions {
+        ConnectionOptions::default()
+    }
+
+    /// Flags used to open the connection.
+    pub fn flags(mut self, flags: OpenFlags) -> ConnectionOptions {
+        self.flags = flags;
+        self
+    }
+
+    /// See `Connection::busy_timeout`.
+    pub fn busy_timeout(mut self, timeout: Duration) -> ConnectionOptions {
+        self.busy_timeout = Some(timeout);
+        self
+    }
+
+    /// See `Connection::set_page_size`.
+    pub fn page_size(mut self, size: u32) -> ConnectionOptions {
+        self.page_size = Some(size);
+        self
+    }
+
+    /// See `Connection::set_auto_vacuum`.
+    pub fn auto_vacuum(mut self, auto_vacuum: AutoVacuum) -> ConnectionOptions {
+        self.auto_vacuum = Some(auto_vacuum);
+        self
+    }
+
+    /// See `Connection::set_locking_mode`.
+    pub fn locking_mode(mut self, mode: LockingMode) -> ConnectionOptions {
+        self.locking_mode = Some(mode);
+        self
+    }
+
+    /// See `Connection::set_journal_mode`.
+    ///
+    /// Opening fails if SQLite does not switch to `mode`.
+    pub fn journal_mode(mut self, mode: JournalMode) -> ConnectionOptions {
+        self.journal_mode = Some(mode);
+        self
+    }
+
+    /// See `Connection::set_synchronous`.
+    pub fn synchronous(mut self, synchronous: Synchronous) -> ConnectionOptions {
+        self.synchronous = Some(synchronous);
+        self
+    }
+
+    /// See `Connection::set_foreign_keys_enabled`.
+    pub fn foreign_keys(mut self, enabled: bool) -> ConnectionOptions {
+        self.foreign_keys = Some(enabled);
+        self
+    }
+
+    /// See `Connection::set_cache_size`.
+    pub fn cache_size(mut self, size: i64) -> ConnectionOptions {
+        self.cache_size = Some(size);
+        self
+    }
+
+    /// See `Connection::set_mmap_size`.
+    pub fn mmap_size(mut self, size: i64) -> ConnectionOptions {
+        self.mmap_size = Some(size);
+        self
+    }
+
+    /// See `Connection::set_temp_store`.
+    pub fn temp_store(mut self, temp_store: TempStore) -> ConnectionOptions {
+        self.temp_store = Some(temp_store);
+        self
+    }
+
+    /// See `Connection::set_application_id`.
+    pub fn application_id(mut self, id: i32) -> ConnectionOptions {
+        self.application_id = Some(id);
+        self
+    }
+
+    /// Open a connection to the database at `path` and apply these options.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the connection cannot be opened or if any of the
+    /// pragmas fails.
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> Result<Connection> {
+        let conn = Connection::open_with_flags(path, self.flags)?;
+        self.apply(&conn)?;
+        Ok(conn)
+    }
+
+    /// Open a connection to an in-memory database and apply these options.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the connection cannot be opened or if any of the
+    /// pragmas fails.
+    pub fn open_in_memory(&self) -> Result<Connection> {
+        let conn = Connection::open_in_memory_with_flags(self.flags)?;
+        self.apply(&conn)?;
+        Ok(conn)
+    }
+
+    /// Apply these options, except for the open flags, to an existing
+    /// connection.
+    ///
+    /// The page size and auto-vacuum mode are set first, since they must
+    /// precede the switch to WAL mode to have any effect.
+    pub fn apply(&self, conn: &Connection) -> Result<()> {
+        if let Some(timeout) = self.busy_timeout {
+            conn.busy_timeout(timeout)?;
+        }
+        if let Some(size) = self.page_size {
+            conn.set_page_size(size)?;
+        }
+        if let Some(auto_vacuum) = self.auto_vacuum {
+            conn.set_auto_vacuum(auto_vacuum)?;
+        }
+        if let Some(mode) = self.locking_mode {
+            conn.set_locking_mode(mode)?;
+        }
+        if let Some(mode) = self.journal_mode {
+            let actual = conn.set_journal_mode(mode)?;
+            if actual != mode {
+                return Err(Error::SqliteFailure(
+                    ffi::Error::new(ffi::SQLITE_ERROR),
+                    Some(format!(
+                        "cannot change journal_mode to {}, still {}",
+                        mode.as_str(),
+                        actual.as_str()
+                    )),
+                ));
+            }
+        }
+        if let Some(synchronous) = self.synchronous {
+            conn.set_synchronous(synchronous)?;
+        }
+        if let Some(enabled) = self.foreign_keys {
+            conn.set_foreign_keys_enabled(enabled)?;
+        }
+        if let Some(size) = self.cache_size {
+            conn.set_cache_size(size)?;
+        }
+        if let Some(size) = self.mmap_size {
+            conn.set_mmap_size(size)?;
+        }
+        if let Some(temp_store) = self.temp_store {
+            conn.set_temp_store(temp_store)?;
+        }
+        if let Some(id) = self.application_id {
+            conn.set_application_id(id)?;
+        }
+        Ok(())
+    }
+}
+
+/// Result of `Connection::integrity_check` or `Connection::quick_check`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IntegrityReport {
+    errors: Vec<String>,
+}
+
+impl IntegrityReport {
+    /// Returns `true` when no problem was found.
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Problems found, one message per row returned by SQLite.
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+}
+
+/// A row which violates a foreign key constraint, as reported by
+/// [`PRAGMA foreign_key_check`](https://sqlite.org/pragma.html#pragma_foreign_key_check).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ForeignKeyViolation {
+    /// Table containing the offending row.
+    pub table: String,
+    /// Rowid of the offending row, `None` for a `WITHOUT ROWID` table.
+    pub rowid: Option<i64>,
+    /// Table referred to by the violated constraint.
+    pub parent: String,
+    /// Index of the violated constraint, matching the `id` column of
+    /// `PRAGMA foreign_key_list`.
+    pub fk_index: i64,
+}
+
+impl Connection {
+    /// Check the integrity of `schema_name`, reporting at most `max_errors`
+    /// problems.
+    ///
+    /// A corrupted database is reported in the returned `IntegrityReport`.
+    /// `Err` is only returned if the check itself cannot run.
+    pub fn integrity_check(
+        &self,
+        schema_name: DatabaseName<'_>,
+        max_errors: u32,
+    ) -> Result<IntegrityReport> {
+        self.run_integrity_check(schema_name, "integrity_check", max_errors)
+    }
+
+    /// Like `integrity_check` but skips the slowest verifications, such as
+    /// checking that index content matches table content.
+    pub fn quick_check(
+        &self,
+        schema_name: DatabaseName<'_>,
+        max_errors: u32,
+    ) -> Result<IntegrityReport> {
+        self.run_integrity_check(schema_name, "quick_check", max_errors)
+    }
+
+    fn run_integrity_check(
+        &self,
+        schema_name: DatabaseName<'_>,
+        pragma_name: &str,
+        max_errors: u32,
+    ) -> Result<IntegrityReport> {
+        let mut errors = Vec::new();
+        self.pragma(Some(schema_name), pragma_name, &max_errors, |row| {
+            let message: String = row.get(0)?;
+            if message != "ok" {
+                errors.push(message);
+            }
+            Ok(())
+        })?;
+        Ok(IntegrityReport { errors })
+    }
+
+    /// Check foreign key constraints of `table`, or of all tables when
+    /// `None`.
+    ///
+    /// This works even when foreign key enforcement is disabled.
+    pub fn foreign_key_check(&self, table: Option<&str>) -> Result<Vec<ForeignKeyViolation>> {
+        let mut violations = Vec::new();
+        let mut f = |row: &Row<'_>| {
+            violations.push(ForeignKeyViolation {
+                table: row.get(0)?,
+                rowid: row.get(1)?,
+                parent: row.get(2)?,
+                fk_index: row.get(3)?,
+            });
+            Ok(())
+        };
+        match table {
+            Some(table) => self.pragma(None, "foreign_key_check", &table, f)?,
+            None => self.pragma_query(None, "foreign_key_check", &mut f)?,
+        }
+        Ok(violations)
+    }
+}
+
 fn is_identifier(s: &str) -> bool {
     let chars = s.char_indices();
     for (i, ch) in chars {
@@ -312,9 +879,11 @@ fn is_identifier_continue(c: char) -> bool {
 
 #[cfg(test)]
 mod test {
-    use super::Sql;
+    use super::{
+        AutoVacuum, ConnectionOptions, JournalMode, LockingMode, Sql, Synchronous, TempStore,
+    };
     use crate::pragma;
-    use crate::{Connection, DatabaseName};
+    use crate::{Connection, DatabaseName, Error, ErrorCode, ForeignKeyViolation, NO_PARAMS};
 
     #[test]
     fn pragma_query_value() {
@@ -430,4 +999,149 @@ mod test {
         sql.push_string_literal("value'; --");
         assert_eq!("'value''; --'", sql.as_str());
     }
+
+    #[test]
+    fn typed_pragmas() {
+        let db = Connection::open_in_memory().unwrap();
+        assert_eq!(JournalMode::Memory, db.journal_mode().unwrap());
+        assert_eq!(
+            JournalMode::Off,
+            db.set_journal_mode(JournalMode::Off).unwrap()
+        );
+        // WAL is not available for in-memory databases
+        assert_eq!(
+            JournalMode::Off,
+            db.set_journal_mode(JournalMode::Wal).unwrap()
+        );
+
+        db.set_synchronous(Synchronous::Extra).unwrap();
+        assert_eq!(Synchronous::Extra, db.synchronous().unwrap());
+        db.set_foreign_keys_enabled(true).unwrap();
+        assert!(db.foreign_keys_enabled().unwrap());
+        db.set_cache_size(-4096).unwrap();
+        assert_eq!(-4096, db.cache_size().unwrap());
+        db.set_temp_store(TempStore::Memory).unwrap();
+        assert_eq!(TempStore::Memory, db.temp_store().unwrap());
+        db.set_page_size(8192).unwrap();
+        db.set_auto_vacuum(AutoVacuum::Incremental).unwrap();
+        db.execute_batch("CREATE TABLE foo(x)").unwrap();
+        assert_eq!(8192, db.page_size().unwrap());
+        assert_eq!(AutoVacuum::Incremental, db.auto_vacuum().unwrap());
+        assert_eq!(
+            LockingMode::Exclusive,
+            db.set_locking_mode(LockingMode::Exclusive).unwrap()
+        );
+        db.set_application_id(-42).unwrap();
+        assert_eq!(-42, db.application_id().unwrap());
+    }
+
+    #[test]
+    fn connection_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("options.db3");
+        let options = ConnectionOptions::new()
+            .page_size(16384)
+            .journal_mode(JournalMode::Wal)
+            .synchronous(Synchronous::Normal)
+            .foreign_keys(true)
+            .application_id(0x5157_4c54);
+        let db = options.open(&path).unwrap();
+        db.execute_batch("CREATE TABLE foo(x)").unwrap();
+        assert_eq!(16384, db.page_size().unwrap());
+        assert_eq!(JournalMode::Wal, db.journal_mode().unwrap());
+        assert_eq!(Synchronous::Normal, db.synchronous().unwrap());
+        assert!(db.foreign_keys_enabled().unwrap());
+        drop(db);
+
+        let db = Connection::open(&path).unwrap();
+        assert_eq!(0x5157_4c54, db.application_id().unwrap());
+        assert_eq!(JournalMode::Wal, db.journal_mode().unwrap());
+    }
+
+    #[test]
+    fn connection_options_journal_mode_mismatch() {
+        let err = ConnectionOptions::new()
+            .journal_mode(JournalMode::Wal)
+            .open_in_memory()
+            .unwrap_err();
+        match err {
+            Error::SqliteFailure(err, _) => assert_eq!(ErrorCode::Unknown, err.code),
+            err => panic!("unexpected error {}", err),
+        }
+    }
+
+    #[test]
+    fn integrity_check() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch("CREATE TABLE foo(x); CREATE INDEX foo_x ON foo(x);")
+            .unwrap();
+        let report = db.integrity_check(DatabaseName::Main, 10).unwrap();
+        assert!(report.is_ok());
+        assert!(db.quick_check(DatabaseName::Main, 10).unwrap().is_ok());
+    }
+
+    #[test]
+    fn integrity_check_corrupted() {
+        use std::fs::OpenOptions;
+        use std::io::{Seek, SeekFrom, Write};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("corrupt.db3");
+        let page_size;
+        let root_page: i64;
+        {
+            let db = Connection::open(&path).unwrap();
+            db.execute_batch(
+                "CREATE TABLE foo(x INTEGER);
+                 CREATE INDEX foo_x ON foo(x);
+                 WITH RECURSIVE c(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM c WHERE i < 100)
+                 INSERT INTO foo SELECT i FROM c;",
+            )
+            .unwrap();
+            page_size = u64::from(db.page_size().unwrap());
+            root_page = db
+                .query_row(
+                    "SELECT rootpage FROM sqlite_master WHERE name = 'foo_x'",
+                    NO_PARAMS,
+                    |row| row.get(0),
+                )
+                .unwrap();
+        }
+        // scribble over the cell content at the end of the index page
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(root_page as u64 * page_size - 200))
+            .unwrap();
+        file.write_all(&[0x55; 100]).unwrap();
+        drop(file);
+
+        let db = Connection::open(&path).unwrap();
+        let report = db.integrity_check(DatabaseName::Main, 3).unwrap();
+        assert!(!report.is_ok());
+        assert!(report.errors().len() <= 3);
+    }
+
+    #[test]
+    fn foreign_key_check() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(
+            "CREATE TABLE parent(id INTEGER PRIMARY KEY);
+             CREATE TABLE child(id INTEGER PRIMARY KEY, parent_id REFERENCES parent(id));
+             CREATE TABLE other(parent_id REFERENCES parent(id));
+             INSERT INTO parent VALUES (1);
+             INSERT INTO child VALUES (1, 1), (2, 2);
+             INSERT INTO other VALUES (3);",
+        )
+        .unwrap();
+        let violations = db.foreign_key_check(Some("child")).unwrap();
+        assert_eq!(
+            vec![ForeignKeyViolation {
+                table: "child".to_owned(),
+                rowid: Some(2),
+                parent: "parent".to_owned(),
+                fk_index: 0,
+            }],
+            violations
+        );
+        assert_eq!(2, db.foreign_key_check(None).unwrap().len());
+    }
 }
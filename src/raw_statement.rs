@@ -41,6 +41,21 @@ impl RawStatement {
         }
     }
 
+    #[cfg(feature = "column_metadata")]
+    pub fn column_database_name(&self, idx: usize) -> Option<&CStr> {
+        unsafe { to_cstr(ffi::sqlite3_column_database_name(self.0, idx as c_int)) }
+    }
+
+    #[cfg(feature = "column_metadata")]
+    pub fn column_table_name(&self, idx: usize) -> Option<&CStr> {
+        unsafe { to_cstr(ffi::sqlite3_column_table_name(self.0, idx as c_int)) }
+    }
+
+    #[cfg(feature = "column_metadata")]
+    pub fn column_origin_name(&self, idx: usize) -> Option<&CStr> {
+        unsafe { to_cstr(ffi::sqlite3_column_origin_name(self.0, idx as c_int)) }
+    }
+
     pub fn column_name(&self, idx: usize) -> Option<&CStr> {
         let idx = idx as c_int;
         if idx < 0 || idx >= self.column_count() as c_int {
@@ -143,6 +158,15 @@ impl RawStatement {
     }
 }
 
+#[cfg(feature = "column_metadata")]
+unsafe fn to_cstr<'a>(ptr: *const std::os::raw::c_char) -> Option<&'a CStr> {
+    if ptr.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(ptr))
+    }
+}
+
 impl Drop for RawStatement {
     fn drop(&mut self) {
         self.finalize_();
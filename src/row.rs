@@ -181,6 +181,17 @@ impl<'stmt> FallibleStreamingIterator for Rows<'stmt> {
     }
 }
 
+/// A type which can be built from a single result row.
+///
+/// With the `derive` feature, it can be implemented with `#[derive(FromRow)]`:
+/// fields are read by column name (or by index for tuple structs), and the
+/// `#[rusqlite(rename = "column")]`, `#[rusqlite(index = N)]`,
+/// `#[rusqlite(skip)]` and `#[rusqlite(default)]` attributes are supported.
+pub trait FromRow: Sized {
+    /// Build a value from `row`.
+    fn from_row(row: &Row<'_>) -> Result<Self>;
+}
+
 /// A single result row of a query.
 pub struct Row<'stmt> {
     pub(crate) stmt: &'stmt Statement<'stmt>,
@@ -0,0 +1,570 @@
+//! Typed access to the database schema: tables, columns, indexes, foreign
+//! keys, triggers and views.
+//!
+//! ```rust,no_run
+//! # use rusqlite::{Connection, Result};
+//! fn print_schema(conn: &Connection) -> Result<()> {
+//!     for table in conn.tables(None)? {
+//!         println!("{}", table.name);
+//!         for column in conn.table_info(None, &table.name)? {
+//!             println!("  {} {:?}", column.name, column.decl_type);
+//!         }
+//!     }
+//!     Ok(())
+//! }
+//! ```
+#[cfg(feature = "column_metadata")]
+use std::ffi::CStr;
+#[cfg(feature = "column_metadata")]
+use std::os::raw::{c_char, c_int};
+#[cfg(feature = "column_metadata")]
+use std::ptr;
+
+#[cfg(feature = "column_metadata")]
+use crate::ffi;
+use crate::pragma::Sql;
+#[cfg(feature = "column_metadata")]
+use crate::str_to_cstring;
+use crate::{Connection, DatabaseName, Result, Row};
+
+/// A table, as listed by [`Connection::tables`](../struct.Connection.html#method.tables).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Table {
+    /// Table name.
+    pub name: String,
+    /// `CREATE TABLE` (or `CREATE VIRTUAL TABLE`) statement.
+    pub sql: Option<String>,
+}
+
+/// How a column is hidden.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnHidden {
+    /// Ordinary column.
+    Normal,
+    /// Hidden column of a virtual table.
+    Hidden,
+    /// Generated column, computed when read.
+    VirtualGenerated,
+    /// Generated column, stored in the table.
+    StoredGenerated,
+}
+
+impl ColumnHidden {
+    fn from_i64(hidden: i64) -> ColumnHidden {
+        match hidden {
+            1 => ColumnHidden::Hidden,
+            2 => ColumnHidden::VirtualGenerated,
+            3 => ColumnHidden::StoredGenerated,
+            _ => ColumnHidden::Normal,
+        }
+    }
+}
+
+/// A column of a table, as returned by
+/// [`Connection::table_info`](../struct.Connection.html#method.table_info).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColumnInfo {
+    /// Column name.
+    pub name: String,
+    /// Declared type, if any.
+    pub decl_type: Option<String>,
+    /// `true` if the column has a `NOT NULL` constraint.
+    pub not_null: bool,
+    /// Text of the default value expression, if any.
+    pub default: Option<String>,
+    /// 1-based position of the column in the primary key, or 0 if the column
+    /// is not part of the primary key.
+    pub primary_key: usize,
+    /// Whether the column is hidden or generated.
+    pub hidden: ColumnHidden,
+}
+
+/// What created an index.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexOrigin {
+    /// A `CREATE INDEX` statement.
+    CreateIndex,
+    /// A `UNIQUE` constraint.
+    Unique,
+    /// A `PRIMARY KEY` constraint.
+    PrimaryKey,
+}
+
+/// An index of a table, as returned by
+/// [`Connection::indexes`](../struct.Connection.html#method.indexes).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Index {
+    /// Index name.
+    pub name: String,
+    /// `true` if the index is unique.
+    pub unique: bool,
+    /// What created the index.
+    pub origin: IndexOrigin,
+    /// `true` if the index is partial (has a `WHERE` clause).
+    pub partial: bool,
+}
+
+/// A column of an index, as returned by
+/// [`Connection::index_columns`](../struct.Connection.html#method.index_columns).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexColumn {
+    /// Name of the indexed table column, or `None` for the rowid and
+    /// expressions.
+    pub name: Option<String>,
+    /// Index of the column in the table, -1 for the rowid and -2 for
+    /// expressions.
+    pub cid: i64,
+    /// `true` if the column is sorted in descending order.
+    pub descending: bool,
+    /// Name of the collating sequence.
+    pub collation: Option<String>,
+    /// `true` for key columns, `false` for auxiliary columns (like the
+    /// rowid appended to each entry).
+    pub key: bool,
+}
+
+/// A (possibly multi-column) foreign key, as returned by
+/// [`Connection::foreign_keys`](../struct.Connection.html#method.foreign_keys).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ForeignKey {
+    /// Identifier of the foreign key within its table.
+    pub id: i64,
+    /// Referenced table.
+    pub table: String,
+    /// Pairs of (child column, parent column). The parent column is `None`
+    /// when the foreign key references the primary key of the parent table
+    /// implicitly.
+    pub columns: Vec<(String, Option<String>)>,
+    /// `ON UPDATE` action (e.g. `NO ACTION` or `CASCADE`).
+    pub on_update: String,
+    /// `ON DELETE` action.
+    pub on_delete: String,
+    /// `MATCH` clause (`NONE` by default).
+    pub match_: String,
+}
+
+/// A trigger, as listed by [`Connection::triggers`](../struct.Connection.html#method.triggers).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Trigger {
+    /// Trigger name.
+    pub name: String,
+    /// Table (or view) the trigger is attached to.
+    pub table: String,
+    /// `CREATE TRIGGER` statement.
+    pub sql: Option<String>,
+}
+
+/// A view, as listed by [`Connection::views`](../struct.Connection.html#method.views).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct View {
+    /// View name.
+    pub name: String,
+    /// `CREATE VIEW` statement.
+    pub sql: Option<String>,
+}
+
+/// Metadata of a table column, as returned by
+/// [`Connection::table_column_metadata`](../struct.Connection.html#method.table_column_metadata).
+#[cfg(feature = "column_metadata")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColumnMetadata {
+    /// Declared type, if any.
+    pub decl_type: Option<String>,
+    /// Name of the default collating sequence.
+    pub collation: Option<String>,
+    /// `true` if the column has a `NOT NULL` constraint.
+    pub not_null: bool,
+    /// `true` if the column is part of the primary key.
+    pub primary_key: bool,
+    /// `true` if the column is `AUTOINCREMENT`.
+    pub auto_increment: bool,
+}
+
+fn non_empty(s: Option<String>) -> Option<String> {
+    s.filter(|s| !s.is_empty())
+}
+
+impl Connection {
+    /// List the tables of `schema_name` (`main` by default), excluding the
+    /// internal `sqlite_` tables, ordered by name.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the schema cannot be read.
+    pub fn tables(&self, schema_name: Option<DatabaseName<'_>>) -> Result<Vec<Table>> {
+        self.query_master(schema_name, "table", |row| {
+            Ok(Table {
+                name: row.get(0)?,
+                sql: row.get(2)?,
+            })
+        })
+    }
+
+    /// List the triggers of `schema_name` (`main` by default), ordered by
+    /// name.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the schema cannot be read.
+    pub fn triggers(&self, schema_name: Option<DatabaseName<'_>>) -> Result<Vec<Trigger>> {
+        self.query_master(schema_name, "trigger", |row| {
+            Ok(Trigger {
+                name: row.get(0)?,
+                table: row.get(1)?,
+                sql: row.get(2)?,
+            })
+        })
+    }
+
+    /// List the views of `schema_name` (`main` by default), ordered by name.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the schema cannot be read.
+    pub fn views(&self, schema_name: Option<DatabaseName<'_>>) -> Result<Vec<View>> {
+        self.query_master(schema_name, "view", |row| {
+            Ok(View {
+                name: row.get(0)?,
+                sql: row.get(2)?,
+            })
+        })
+    }
+
+    fn query_master<T, F>(
+        &self,
+        schema_name: Option<DatabaseName<'_>>,
+        kind: &str,
+        f: F,
+    ) -> Result<Vec<T>>
+    where
+        F: FnMut(&Row<'_>) -> Result<T>,
+    {
+        let mut schema = Sql::new();
+        schema.push_schema_name(schema_name.unwrap_or(DatabaseName::Main));
+        let sql = format!(
+            "SELECT name, tbl_name, sql FROM {}.sqlite_master \
+             WHERE type = ? AND name NOT LIKE 'sqlite_%' ORDER BY name",
+            schema.as_str()
+        );
+        let mut stmt = self.prepare(&sql)?;
+        let rows = stmt.query_map(&[kind], f)?;
+        rows.collect()
+    }
+
+    /// Describe the columns of `table`, including hidden and generated
+    /// columns (see `PRAGMA table_xinfo`).
+    ///
+    /// The result is empty if `table` does not exist.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the schema cannot be read.
+    pub fn table_info(
+        &self,
+        schema_name: Option<DatabaseName<'_>>,
+        table: &str,
+    ) -> Result<Vec<ColumnInfo>> {
+        let read = |row: &Row<'_>, hidden: bool| -> Result<ColumnInfo> {
+            Ok(ColumnInfo {
+                name: row.get("name")?,
+                decl_type: non_empty(row.get("type")?),
+                not_null: row.get("notnull")?,
+                default: row.get("dflt_value")?,
+                primary_key: row.get::<_, i64>("pk")? as usize,
+                hidden: if hidden {
+                    ColumnHidden::from_i64(row.get("hidden")?)
+                } else {
+                    ColumnHidden::Normal
+                },
+            })
+        };
+        let mut columns = Vec::new();
+        // table_xinfo: 3.26.0
+        self.pragma(schema_name, "table_xinfo", &table, |row| {
+            columns.push(read(row, true)?);
+            Ok(())
+        })?;
+        if columns.is_empty() {
+            self.pragma(schema_name, "table_info", &table, |row| {
+                columns.push(read(row, false)?);
+                Ok(())
+            })?;
+        }
+        Ok(columns)
+    }
+
+    /// List the indexes of `table`.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the schema cannot be read.
+    pub fn indexes(
+        &self,
+        schema_name: Option<DatabaseName<'_>>,
+        table: &str,
+    ) -> Result<Vec<Index>> {
+        let mut indexes = Vec::new();
+        self.pragma(schema_name, "index_list", &table, |row| {
+            let origin: String = row.get("origin")?;
+            indexes.push(Index {
+                name: row.get("name")?,
+                unique: row.get("unique")?,
+                origin: match origin.as_str() {
+                    "u" => IndexOrigin::Unique,
+                    "pk" => IndexOrigin::PrimaryKey,
+                    _ => IndexOrigin::CreateIndex,
+                },
+                partial: row.get("partial")?,
+            });
+            Ok(())
+        })?;
+        Ok(indexes)
+    }
+
+    /// Describe the columns of `index`, key columns first (see
+    /// `PRAGMA index_xinfo`).
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the schema cannot be read.
+    pub fn index_columns(
+        &self,
+        schema_name: Option<DatabaseName<'_>>,
+        index: &str,
+    ) -> Result<Vec<IndexColumn>> {
+        let mut columns = Vec::new();
+        self.pragma(schema_name, "index_xinfo", &index, |row| {
+            columns.push(IndexColumn {
+                name: row.get("name")?,
+                cid: row.get("cid")?,
+                descending: row.get("desc")?,
+                collation: row.get("coll")?,
+                key: row.get("key")?,
+            });
+            Ok(())
+        })?;
+        Ok(columns)
+    }
+
+    /// List the foreign keys of `table`, ordered by id.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the schema cannot be read.
+    pub fn foreign_keys(
+        &self,
+        schema_name: Option<DatabaseName<'_>>,
+        table: &str,
+    ) -> Result<Vec<ForeignKey>> {
+        let mut keys: Vec<ForeignKey> = Vec::new();
+        self.pragma(schema_name, "foreign_key_list", &table, |row| {
+            let id: i64 = row.get("id")?;
+            let column = (row.get("from")?, row.get("to")?);
+            match keys.last_mut() {
+                Some(key) if key.id == id => key.columns.push(column),
+                _ => keys.push(ForeignKey {
+                    id,
+                    table: row.get("table")?,
+                    columns: vec![column],
+                    on_update: row.get("on_update")?,
+                    on_delete: row.get("on_delete")?,
+                    match_: row.get("match")?,
+                }),
+            }
+            Ok(())
+        })?;
+        keys.sort_by_key(|key| key.id);
+        Ok(keys)
+    }
+
+    /// Read the metadata of `column` in `table` with
+    /// [`sqlite3_table_column_metadata`](https://sqlite.org/c3ref/table_column_metadata.html).
+    ///
+    /// If `schema_name` is `None`, all attached databases are searched.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `table` or `column` does not exist.
+    #[cfg(feature = "column_metadata")]
+    pub fn table_column_metadata(
+        &self,
+        schema_name: Option<DatabaseName<'_>>,
+        table: &str,
+        column: &str,
+    ) -> Result<ColumnMetadata> {
+        let schema_name = match schema_name {
+            Some(schema_name) => Some(schema_name.to_cstring()?),
+            None => None,
+        };
+        let table = str_to_cstring(table)?;
+        let column = str_to_cstring(column)?;
+        let mut decl_type: *const c_char = ptr::null();
+        let mut collation: *const c_char = ptr::null();
+        let mut not_null: c_int = 0;
+        let mut primary_key: c_int = 0;
+        let mut auto_increment: c_int = 0;
+        let mut c = self.db.borrow_mut();
+        let rc = unsafe {
+            ffi::sqlite3_table_column_metadata(
+                c.db(),
+                schema_name.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+                table.as_ptr(),
+                column.as_ptr(),
+                &mut decl_type,
+                &mut collation,
+                &mut not_null,
+                &mut primary_key,
+                &mut auto_increment,
+            )
+        };
+        c.decode_result(rc)?;
+        let to_string = |s: *const c_char| {
+            if s.is_null() {
+                None
+            } else {
+                Some(unsafe { CStr::from_ptr(s) }.to_string_lossy().into_owned())
+            }
+        };
+        Ok(ColumnMetadata {
+            decl_type: to_string(decl_type),
+            collation: to_string(collation),
+            not_null: not_null != 0,
+            primary_key: primary_key != 0,
+            auto_increment: auto_increment != 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ColumnHidden, IndexOrigin};
+    use crate::{Connection, DatabaseName};
+
+    fn schema_db() -> Connection {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(
+            "CREATE TABLE parent (id INTEGER PRIMARY KEY AUTOINCREMENT, a TEXT, b TEXT, UNIQUE (a, b));
+             CREATE TABLE child (
+                 id INTEGER NOT NULL DEFAULT 0,
+                 pa TEXT COLLATE NOCASE,
+                 pb TEXT,
+                 parent_id INTEGER REFERENCES parent ON DELETE CASCADE,
+                 total INTEGER GENERATED ALWAYS AS (id * 2),
+                 PRIMARY KEY (id, pa),
+                 FOREIGN KEY (pa, pb) REFERENCES parent (a, b)
+             );
+             CREATE INDEX child_pb ON child (pb DESC, lower(pa)) WHERE pb IS NOT NULL;
+             CREATE VIEW parents AS SELECT * FROM parent;
+             CREATE TRIGGER child_insert AFTER INSERT ON child BEGIN SELECT 1; END;",
+        )
+        .unwrap();
+        db
+    }
+
+    #[test]
+    fn test_tables_views_triggers() {
+        let db = schema_db();
+        let tables: Vec<_> = db
+            .tables(None)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.name)
+            .collect();
+        assert_eq!(vec!["child", "parent"], tables);
+
+        let views = db.views(Some(DatabaseName::Main)).unwrap();
+        assert_eq!(1, views.len());
+        assert_eq!("parents", views[0].name);
+        assert!(views[0].sql.as_ref().unwrap().starts_with("CREATE VIEW"));
+
+        let triggers = db.triggers(None).unwrap();
+        assert_eq!(1, triggers.len());
+        assert_eq!("child_insert", triggers[0].name);
+        assert_eq!("child", triggers[0].table);
+
+        assert!(db.tables(Some(DatabaseName::Temp)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_table_info() {
+        let db = schema_db();
+        let columns = db.table_info(None, "child").unwrap();
+        assert_eq!(5, columns.len());
+        let id = &columns[0];
+        assert_eq!("id", id.name);
+        assert_eq!(Some("INTEGER"), id.decl_type.as_deref());
+        assert!(id.not_null);
+        assert_eq!(Some("0"), id.default.as_deref());
+        assert_eq!(1, id.primary_key);
+        assert_eq!(2, columns[1].primary_key);
+        assert_eq!(0, columns[2].primary_key);
+        assert_eq!(ColumnHidden::Normal, columns[2].hidden);
+        assert_eq!(ColumnHidden::VirtualGenerated, columns[4].hidden);
+
+        assert!(db.table_info(None, "missing").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_indexes() {
+        let db = schema_db();
+        let mut indexes = db.indexes(None, "child").unwrap();
+        indexes.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(2, indexes.len());
+        assert_eq!("child_pb", indexes[0].name);
+        assert_eq!(IndexOrigin::CreateIndex, indexes[0].origin);
+        assert!(!indexes[0].unique);
+        assert!(indexes[0].partial);
+        assert_eq!(IndexOrigin::PrimaryKey, indexes[1].origin);
+        assert!(indexes[1].unique);
+
+        let columns = db.index_columns(None, "child_pb").unwrap();
+        let keys: Vec<_> = columns.iter().filter(|c| c.key).collect();
+        assert_eq!(2, keys.len());
+        assert_eq!(Some("pb"), keys[0].name.as_deref());
+        assert!(keys[0].descending);
+        assert_eq!(None, keys[1].name);
+        assert_eq!(-2, keys[1].cid);
+
+        let parent = db.indexes(None, "parent").unwrap();
+        assert_eq!(1, parent.len());
+        assert_eq!(IndexOrigin::Unique, parent[0].origin);
+    }
+
+    #[test]
+    fn test_foreign_keys() {
+        let db = schema_db();
+        let keys = db.foreign_keys(None, "child").unwrap();
+        assert_eq!(2, keys.len());
+        let composite = keys.iter().find(|k| k.columns.len() == 2).unwrap();
+        assert_eq!("parent", composite.table);
+        assert_eq!(
+            vec![
+                ("pa".to_owned(), Some("a".to_owned())),
+                ("pb".to_owned(), Some("b".to_owned()))
+            ],
+            composite.columns
+        );
+        let single = keys.iter().find(|k| k.columns.len() == 1).unwrap();
+        assert_eq!(vec![("parent_id".to_owned(), None)], single.columns);
+        assert_eq!("CASCADE", single.on_delete);
+        assert_eq!("NO ACTION", single.on_update);
+        assert_eq!("NONE", single.match_);
+    }
+
+    #[test]
+    #[cfg(feature = "column_metadata")]
+    fn test_table_column_metadata() {
+        let db = schema_db();
+        let id = db.table_column_metadata(None, "parent", "id").unwrap();
+        assert_eq!(Some("INTEGER"), id.decl_type.as_deref());
+        assert!(id.primary_key);
+        assert!(id.auto_increment);
+        let pa = db
+            .table_column_metadata(Some(DatabaseName::Main), "child", "pa")
+            .unwrap();
+        assert_eq!(Some("NOCASE"), pa.collation.as_deref());
+        assert!(!pa.not_null);
+        db.table_column_metadata(None, "child", "missing")
+            .unwrap_err();
+    }
+}
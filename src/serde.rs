@@ -0,0 +1,991 @@
+//! Map result rows to, and named parameters from, types implementing
+//! [serde](https://serde.rs)'s `Deserialize` and `Serialize` traits.
+//!
+//! A `Row` is a `serde::Deserializer`: `row.deserialize::<T>()` reads
+//! structs and maps by column name, and tuples and sequences by column index.
+//! [`to_named_params`](fn.to_named_params.html) converts a struct or a map
+//! into named parameters for `execute_named` and `query_named`.
+//!
+//! ```rust
+//! use rusqlite::serde::to_named_params;
+//! use rusqlite::{Connection, Result, NO_PARAMS};
+//! use serde_derive::{Deserialize, Serialize};
+//!
+//! #[derive(Debug, Deserialize, Serialize)]
+//! struct Person {
+//!     id: i64,
+//!     name: String,
+//!     email: Option<String>,
+//! }
+//!
+//! fn insert_and_list(conn: &Connection, person: &Person) -> Result<Vec<Person>> {
+//!     let params = to_named_params(person)?;
+//!     conn.execute_named(
+//!         "INSERT INTO person (id, name, email) VALUES (:id, :name, :email)",
+//!         &params.to_vec(),
+//!     )?;
+//!     let mut stmt = conn.prepare("SELECT id, name, email FROM person")?;
+//!     let rows = stmt.query_map(NO_PARAMS, |row| row.deserialize())?;
+//!     rows.collect()
+//! }
+//! ```
+use std::fmt::Display;
+use std::str;
+
+use serde::de::value::{BorrowedStrDeserializer, SeqDeserializer};
+use serde::de::{self, Deserialize, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, Impossible, Serialize, SerializeMap, SerializeStruct, Serializer};
+
+use crate::types::{ToSql, Value, ValueRef};
+use crate::{Error, Result, Row};
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Error {
+        Error::DeserializeError(msg.to_string())
+    }
+
+    fn missing_field(field: &'static str) -> Error {
+        Error::InvalidColumnName(field.to_owned())
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Error {
+        Error::ToSqlConversionFailure(msg.to_string().into())
+    }
+}
+
+impl<'stmt> Row<'stmt> {
+    /// Deserialize this row into any type implementing `Deserialize`.
+    ///
+    /// Structs and maps are filled by column name, tuples and sequences by
+    /// column index. A row with a single column can also be deserialized
+    /// into the type of that column.
+    ///
+    /// ## Failure
+    ///
+    /// Returns an `Error::FromSqlConversionFailure` if a column value cannot
+    /// be converted to the type of the matching field, an
+    /// `Error::InvalidColumnName` if a required field has no matching column,
+    /// and an `Error::DeserializeError` if the row does not have the shape of
+    /// `T`.
+    pub fn deserialize<'de, T: Deserialize<'de>>(&'de self) -> Result<T> {
+        T::deserialize(self)
+    }
+}
+
+/// Deserialize the column `idx` of `row` with `seed`, adding the column index
+/// and type to conversion errors.
+fn deserialize_column<'de, T: DeserializeSeed<'de>>(
+    row: &'de Row<'_>,
+    idx: usize,
+    seed: T,
+) -> Result<T::Value> {
+    let value = row.get_raw_checked(idx)?;
+    seed.deserialize(ValueDeserializer { value })
+        .map_err(|err| column_error(idx, value, err))
+}
+
+fn column_error(idx: usize, value: ValueRef<'_>, err: Error) -> Error {
+    match err {
+        Error::DeserializeError(msg) => {
+            Error::FromSqlConversionFailure(idx, value.data_type(), msg.into())
+        }
+        err => err,
+    }
+}
+
+macro_rules! forward_to_single_column {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+                let value = self.single_column()?;
+                ValueDeserializer { value }
+                    .$method(visitor)
+                    .map_err(|err| column_error(0, value, err))
+            }
+        )*
+    };
+}
+
+impl<'stmt> Row<'stmt> {
+    fn single_column(&self) -> Result<ValueRef<'_>> {
+        match self.column_count() {
+            1 => self.get_raw_checked(0),
+            n => Err(de::Error::custom(format!(
+                "expected a single column, got {}",
+                n
+            ))),
+        }
+    }
+}
+
+impl<'de, 'stmt> Deserializer<'de> for &'de Row<'stmt> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(RowAccess { row: self, idx: 0 })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_map(RowAccess { row: self, idx: 0 })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let value = self.single_column()?;
+        ValueDeserializer { value }
+            .deserialize_enum(name, variants, visitor)
+            .map_err(|err| column_error(0, value, err))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    forward_to_single_column! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_f32 deserialize_f64 deserialize_char deserialize_str deserialize_string
+        deserialize_bytes deserialize_byte_buf deserialize_identifier
+    }
+}
+
+/// Access to the columns of a row, as a map (by name) or as a sequence.
+struct RowAccess<'de, 'stmt> {
+    row: &'de Row<'stmt>,
+    idx: usize,
+}
+
+impl<'de> RowAccess<'de, '_> {
+    fn remaining(&self) -> usize {
+        self.row.column_count() - self.idx
+    }
+}
+
+impl<'de> MapAccess<'de> for RowAccess<'de, '_> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if self.remaining() == 0 {
+            return Ok(None);
+        }
+        let name = self.row.column_name(self.idx)?;
+        seed.deserialize(BorrowedStrDeserializer::new(name))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let idx = self.idx;
+        self.idx += 1;
+        deserialize_column(self.row, idx, seed)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining())
+    }
+}
+
+impl<'de> SeqAccess<'de> for RowAccess<'de, '_> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if self.remaining() == 0 {
+            return Ok(None);
+        }
+        let idx = self.idx;
+        self.idx += 1;
+        deserialize_column(self.row, idx, seed).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining())
+    }
+}
+
+/// Deserializer for a single column value.
+struct ValueDeserializer<'de> {
+    value: ValueRef<'de>,
+}
+
+impl<'de> Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            ValueRef::Null => visitor.visit_unit(),
+            ValueRef::Integer(i) => visitor.visit_i64(i),
+            ValueRef::Real(f) => visitor.visit_f64(f),
+            ValueRef::Text(t) => match str::from_utf8(t) {
+                Ok(s) => visitor.visit_borrowed_str(s),
+                Err(_) => visitor.visit_borrowed_bytes(t),
+            },
+            ValueRef::Blob(b) => visitor.visit_borrowed_bytes(b),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            ValueRef::Integer(i) => visitor.visit_bool(i != 0),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            ValueRef::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // `Vec<u8>` is deserialized as a sequence.
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            ValueRef::Blob(b) => visitor.visit_seq(SeqDeserializer::new(b.iter().cloned())),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    // Only unit variants, stored as their name.
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self.value {
+            ValueRef::Text(t) => {
+                let variant = str::from_utf8(t)?;
+                visitor.visit_enum(BorrowedStrDeserializer::new(variant))
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf
+        unit unit_struct tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// Named parameters built by [`to_named_params`](fn.to_named_params.html).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NamedParams {
+    params: Vec<(String, Value)>,
+}
+
+impl NamedParams {
+    /// Parameters in the form expected by `execute_named` and
+    /// `query_named`.
+    pub fn to_vec(&self) -> Vec<(&str, &dyn ToSql)> {
+        self.params
+            .iter()
+            .map(|(name, value)| (name.as_str(), value as &dyn ToSql))
+            .collect()
+    }
+}
+
+/// Convert a struct or a map into named parameters.
+///
+/// Each field (or key) `name` becomes the parameter `:name`, unless it
+/// already starts with `:`, `@` or `$`. Field values must be scalars:
+/// integers, floats, strings, bytes, `Option`s, newtypes or unit enum
+/// variants (bound as their name).
+///
+/// Note that `execute_named` and `query_named` fail with
+/// `Error::InvalidParameterName` on parameters not used by the statement.
+///
+/// ## Failure
+///
+/// Returns an `Error::ToSqlConversionFailure` if `value` is not a struct or
+/// a map, or if a field value is not a scalar.
+pub fn to_named_params<T: Serialize + ?Sized>(value: &T) -> Result<NamedParams> {
+    value.serialize(ParamsSerializer)
+}
+
+fn param_name(name: String) -> String {
+    if name.starts_with(&[':', '@', '$'][..]) {
+        name
+    } else {
+        format!(":{}", name)
+    }
+}
+
+fn unsupported(what: &str) -> Error {
+    ser::Error::custom(format!("{} cannot be bound as a parameter", what))
+}
+
+/// Serializer of a struct or a map into `NamedParams`.
+struct ParamsSerializer;
+
+fn not_named(what: &str) -> Error {
+    ser::Error::custom(format!(
+        "expected a struct or a map of named parameters, got {}",
+        what
+    ))
+}
+
+impl Serializer for ParamsSerializer {
+    type Ok = NamedParams;
+    type Error = Error;
+    type SerializeSeq = Impossible<NamedParams, Error>;
+    type SerializeTuple = Impossible<NamedParams, Error>;
+    type SerializeTupleStruct = Impossible<NamedParams, Error>;
+    type SerializeTupleVariant = Impossible<NamedParams, Error>;
+    type SerializeMap = ParamsBuilder;
+    type SerializeStruct = ParamsBuilder;
+    type SerializeStructVariant = Impossible<NamedParams, Error>;
+
+    fn serialize_bool(self, _: bool) -> Result<NamedParams> {
+        Err(not_named("a boolean"))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<NamedParams> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<NamedParams> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<NamedParams> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i64(self, _: i64) -> Result<NamedParams> {
+        Err(not_named("an integer"))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<NamedParams> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<NamedParams> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<NamedParams> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u64(self, _: u64) -> Result<NamedParams> {
+        Err(not_named("an integer"))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<NamedParams> {
+        self.serialize_f64(f64::from(v))
+    }
+
+    fn serialize_f64(self, _: f64) -> Result<NamedParams> {
+        Err(not_named("a float"))
+    }
+
+    fn serialize_char(self, _: char) -> Result<NamedParams> {
+        Err(not_named("a string"))
+    }
+
+    fn serialize_str(self, _: &str) -> Result<NamedParams> {
+        Err(not_named("a string"))
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> Result<NamedParams> {
+        Err(not_named("bytes"))
+    }
+
+    fn serialize_none(self) -> Result<NamedParams> {
+        Ok(NamedParams::default())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<NamedParams> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<NamedParams> {
+        Ok(NamedParams::default())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<NamedParams> {
+        Ok(NamedParams::default())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<NamedParams> {
+        Err(not_named("an enum"))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<NamedParams> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<NamedParams> {
+        Err(not_named("an enum"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(not_named("a sequence"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(not_named("a tuple"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(not_named("a tuple struct"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(not_named("an enum"))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<ParamsBuilder> {
+        Ok(ParamsBuilder {
+            params: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<ParamsBuilder> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(not_named("an enum"))
+    }
+}
+
+/// Collects the fields of a struct or the entries of a map.
+struct ParamsBuilder {
+    params: Vec<(String, Value)>,
+    key: Option<String>,
+}
+
+impl SerializeMap for ParamsBuilder {
+    type Ok = NamedParams;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        match key.serialize(ValueSerializer)? {
+            Value::Text(name) => {
+                self.key = Some(param_name(name));
+                Ok(())
+            }
+            _ => Err(ser::Error::custom("parameter names must be strings")),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let name = self.key.take().ok_or_else(|| {
+            <Error as ser::Error>::custom("serialize_value called before serialize_key")
+        })?;
+        let value = value.serialize(ValueSerializer)?;
+        self.params.push((name, value));
+        Ok(())
+    }
+
+    fn end(self) -> Result<NamedParams> {
+        Ok(NamedParams {
+            params: self.params,
+        })
+    }
+}
+
+impl SerializeStruct for ParamsBuilder {
+    type Ok = NamedParams;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        let value = value.serialize(ValueSerializer)?;
+        self.params.push((param_name(key.to_owned()), value));
+        Ok(())
+    }
+
+    fn end(self) -> Result<NamedParams> {
+        SerializeMap::end(self)
+    }
+}
+
+/// Serializer of a single parameter value.
+struct ValueSerializer;
+
+impl Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = Error;
+    type SerializeSeq = Impossible<Value, Error>;
+    type SerializeTuple = Impossible<Value, Error>;
+    type SerializeTupleStruct = Impossible<Value, Error>;
+    type SerializeTupleVariant = Impossible<Value, Error>;
+    type SerializeMap = Impossible<Value, Error>;
+    type SerializeStruct = Impossible<Value, Error>;
+    type SerializeStructVariant = Impossible<Value, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<Value> {
+        Ok(Value::Integer(v as i64))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value> {
+        Ok(Value::Integer(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value> {
+        if v > i64::MAX as u64 {
+            return Err(ser::Error::custom(format!(
+                "integer {} out of range of i64",
+                v
+            )));
+        }
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value> {
+        self.serialize_f64(f64::from(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value> {
+        Ok(Value::Real(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value> {
+        Ok(Value::Text(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value> {
+        Ok(Value::Text(v.to_owned()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value> {
+        Ok(Value::Blob(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<Value> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Value> {
+        Err(unsupported("an enum variant with data"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(unsupported("a sequence"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(unsupported("a tuple"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(unsupported("a tuple struct"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(unsupported("an enum variant with data"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(unsupported("a map"))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(unsupported("a struct"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(unsupported("an enum variant with data"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{BTreeMap, HashMap};
+
+    use serde_derive::{Deserialize, Serialize};
+
+    use super::to_named_params;
+    use crate::types::{Type, Value};
+    use crate::{Connection, Error, NO_PARAMS};
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct UserId(i64);
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Role {
+        Admin,
+        Guest,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct User {
+        id: UserId,
+        name: String,
+        email: Option<String>,
+        role: Role,
+        active: bool,
+        #[serde(with = "bytes")]
+        avatar: Vec<u8>,
+    }
+
+    // `Vec<u8>` is serialized as a sequence by default.
+    mod bytes {
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(v: &[u8], s: S) -> Result<S::Ok, S::Error> {
+            s.serialize_bytes(v)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+            Vec::deserialize(d)
+        }
+    }
+
+    fn user_db() -> Connection {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(
+            "CREATE TABLE user (id INTEGER PRIMARY KEY, name TEXT, email TEXT, role TEXT, active INTEGER, avatar BLOB);",
+        )
+        .unwrap();
+        db
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let db = user_db();
+        let user = User {
+            id: UserId(1),
+            name: "alice".to_owned(),
+            email: None,
+            role: Role::Admin,
+            active: true,
+            avatar: vec![1, 2, 3],
+        };
+        let params = to_named_params(&user).unwrap();
+        db.execute_named(
+            "INSERT INTO user VALUES (:id, :name, :email, :role, :active, :avatar)",
+            &params.to_vec(),
+        )
+        .unwrap();
+
+        let role: String = db
+            .query_row("SELECT role FROM user", NO_PARAMS, |r| r.get(0))
+            .unwrap();
+        assert_eq!("admin", role);
+
+        let mut stmt = db.prepare("SELECT * FROM user").unwrap();
+        let mut rows = stmt.query(NO_PARAMS).unwrap();
+        let row = rows.next().unwrap().unwrap();
+        assert_eq!(user, row.deserialize::<User>().unwrap());
+    }
+
+    #[test]
+    fn test_deserialize_shapes() {
+        let db = Connection::open_in_memory().unwrap();
+        let mut stmt = db
+            .prepare("SELECT 1 AS a, 'two' AS b, NULL AS c, 4.5 AS d")
+            .unwrap();
+        let mut rows = stmt.query(NO_PARAMS).unwrap();
+        let row = rows.next().unwrap().unwrap();
+
+        let (a, b, c, d): (i64, &str, Option<i64>, f64) = row.deserialize().unwrap();
+        assert_eq!((1, "two", None, 4.5), (a, b, c, d));
+
+        let map: HashMap<&str, any::Any> = row.deserialize().unwrap();
+        assert_eq!(4, map.len());
+        assert_eq!(Some("1"), map["a"].0.as_deref());
+        assert_eq!(Some("two"), map["b"].0.as_deref());
+        assert_eq!(None, map["c"].0);
+
+        #[derive(Debug, Deserialize)]
+        struct Partial {
+            a: u8,
+            d: f32,
+        }
+        let partial: Partial = row.deserialize().unwrap();
+        assert_eq!(1, partial.a);
+        assert_eq!(4.5, partial.d);
+
+        #[derive(Debug, Deserialize)]
+        struct Wrapper(Partial);
+        let wrapper: Wrapper = row.deserialize().unwrap();
+        assert_eq!(1, (wrapper.0).a);
+
+        // a row with several columns is not a scalar
+        row.deserialize::<i64>().unwrap_err();
+    }
+
+    // Deserializes any column value into its textual form.
+    mod any {
+        use serde::de::{Deserialize, Deserializer, Visitor};
+        use std::fmt;
+
+        pub struct Any(pub Option<String>);
+
+        impl<'de> Deserialize<'de> for Any {
+            fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Any, D::Error> {
+                struct AnyVisitor;
+                impl<'de> Visitor<'de> for AnyVisitor {
+                    type Value = Any;
+
+                    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                        f.write_str("any value")
+                    }
+
+                    fn visit_unit<E>(self) -> Result<Any, E> {
+                        Ok(Any(None))
+                    }
+
+                    fn visit_i64<E>(self, v: i64) -> Result<Any, E> {
+                        Ok(Any(Some(v.to_string())))
+                    }
+
+                    fn visit_f64<E>(self, v: f64) -> Result<Any, E> {
+                        Ok(Any(Some(v.to_string())))
+                    }
+
+                    fn visit_str<E>(self, v: &str) -> Result<Any, E> {
+                        Ok(Any(Some(v.to_owned())))
+                    }
+                }
+                d.deserialize_any(AnyVisitor)
+            }
+        }
+    }
+
+    #[test]
+    fn test_single_column() {
+        let db = Connection::open_in_memory().unwrap();
+        let mut stmt = db.prepare("SELECT 42 AS x").unwrap();
+        let values: Vec<UserId> = stmt
+            .query_map(NO_PARAMS, |r| r.deserialize())
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(vec![UserId(42)], values);
+    }
+
+    #[test]
+    fn test_deserialize_error() {
+        let db = Connection::open_in_memory().unwrap();
+        let mut stmt = db.prepare("SELECT 'one' AS a").unwrap();
+        let mut rows = stmt.query(NO_PARAMS).unwrap();
+        let row = rows.next().unwrap().unwrap();
+
+        #[derive(Debug, Deserialize)]
+        struct A {
+            #[allow(dead_code)]
+            a: i64,
+        }
+        match row.deserialize::<A>().unwrap_err() {
+            Error::FromSqlConversionFailure(0, Type::Text, _) => (),
+            err => panic!("unexpected error {:?}", err),
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct B {
+            #[allow(dead_code)]
+            b: i64,
+        }
+        match row.deserialize::<B>().unwrap_err() {
+            Error::InvalidColumnName(ref name) if name == "b" => (),
+            err => panic!("unexpected error {:?}", err),
+        }
+        match row.deserialize::<(String, String)>().unwrap_err() {
+            Error::DeserializeError(_) => (),
+            err => panic!("unexpected error {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_to_named_params() {
+        let mut map = BTreeMap::new();
+        map.insert("@x", 1u64);
+        map.insert("y", 2);
+        let params = to_named_params(&map).unwrap();
+        let names: Vec<_> = params.to_vec().into_iter().map(|(n, _)| n).collect();
+        assert_eq!(vec!["@x", ":y"], names);
+        assert_eq!(
+            vec![
+                ("@x".to_owned(), Value::Integer(1)),
+                (":y".to_owned(), Value::Integer(2))
+            ],
+            params.params
+        );
+
+        to_named_params(&42).unwrap_err();
+        to_named_params(&(1, 2)).unwrap_err();
+        map.insert("z", u64::MAX);
+        match to_named_params(&map).unwrap_err() {
+            Error::ToSqlConversionFailure(_) => (),
+            err => panic!("unexpected error {:?}", err),
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! Serialize a database to, or deserialize it from, an in-memory buffer.
+//!
+//! See [sqlite3_serialize](https://sqlite.org/c3ref/serialize.html) and
+//! [sqlite3_deserialize](https://sqlite.org/c3ref/deserialize.html).
+//!
+//! ```rust
+//! # use rusqlite::{Connection, DatabaseName, Result};
+//! fn clone_db(src: &Connection) -> Result<Connection> {
+//!     let data = src.serialize(DatabaseName::Main)?;
+//!     let mut dst = Connection::open_in_memory()?;
+//!     dst.deserialize(DatabaseName::Main, data, false)?;
+//!     Ok(dst)
+//! }
+//! ```
+use std::ops::Deref;
+use std::os::raw::c_uint;
+use std::{fmt, ptr, slice};
+
+use crate::error::error_from_sqlite_code;
+use crate::ffi;
+use crate::{Connection, DatabaseName, Error, Result};
+
+/// A buffer allocated by SQLite (with `sqlite3_malloc64`) and freed with
+/// `sqlite3_free` when dropped.
+pub struct OwnedData {
+    ptr: *mut u8,
+    sz: usize,
+}
+
+unsafe impl Send for OwnedData {}
+unsafe impl Sync for OwnedData {}
+
+impl OwnedData {
+    /// Copy `data` into a new buffer allocated by SQLite.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if SQLite cannot allocate the buffer.
+    pub fn from_slice(data: &[u8]) -> Result<OwnedData> {
+        if data.is_empty() {
+            return Ok(OwnedData {
+                ptr: ptr::null_mut(),
+                sz: 0,
+            });
+        }
+        let ptr = unsafe { ffi::sqlite3_malloc64(data.len() as ffi::sqlite3_uint64) } as *mut u8;
+        if ptr.is_null() {
+            return Err(error_from_sqlite_code(ffi::SQLITE_NOMEM, None));
+        }
+        unsafe { ptr::copy_nonoverlapping(data.as_ptr(), ptr, data.len()) };
+        Ok(OwnedData {
+            ptr,
+            sz: data.len(),
+        })
+    }
+
+    /// Take ownership of a buffer of `sz` bytes allocated by SQLite.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be null or have been allocated by `sqlite3_malloc` or
+    /// `sqlite3_malloc64`, and must hold at least `sz` initialized bytes.
+    pub unsafe fn from_raw(ptr: *mut u8, sz: usize) -> OwnedData {
+        OwnedData { ptr, sz }
+    }
+
+    fn into_raw(self) -> (*mut u8, usize) {
+        let raw = (self.ptr, self.sz);
+        std::mem::forget(self);
+        raw
+    }
+}
+
+impl Deref for OwnedData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        if self.ptr.is_null() {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.ptr, self.sz) }
+        }
+    }
+}
+
+impl AsRef<[u8]> for OwnedData {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl fmt::Debug for OwnedData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OwnedData").field("len", &self.sz).finish()
+    }
+}
+
+impl Drop for OwnedData {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe { ffi::sqlite3_free(self.ptr as *mut _) };
+        }
+    }
+}
+
+impl Connection {
+    /// Serialize the `schema` database into a new buffer.
+    ///
+    /// For an ordinary on-disk database, the returned bytes are the same as
+    /// the content of the database file.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `schema` is not an attached database or if
+    /// the buffer cannot be allocated.
+    pub fn serialize(&self, schema: DatabaseName<'_>) -> Result<OwnedData> {
+        let c = self.db.borrow();
+        let name = schema.to_cstring()?;
+        let mut sz: ffi::sqlite3_int64 = 0;
+        let ptr = unsafe { ffi::sqlite3_serialize(c.db(), name.as_ptr(), &mut sz, 0) };
+        if ptr.is_null() {
+            return match sz {
+                // empty database
+                0 => Ok(OwnedData {
+                    ptr: ptr::null_mut(),
+                    sz: 0,
+                }),
+                sz if sz < 0 => Err(Error::SqliteFailure(
+                    ffi::Error::new(ffi::SQLITE_ERROR),
+                    Some(format!("unknown database: {}", name.to_string_lossy())),
+                )),
+                _ => Err(error_from_sqlite_code(ffi::SQLITE_NOMEM, None)),
+            };
+        }
+        Ok(OwnedData {
+            ptr,
+            sz: sz as usize,
+        })
+    }
+
+    /// Close the `schema` database and reopen it as an in-memory database
+    /// holding the content of `data`.
+    ///
+    /// Ownership of `data` is transferred to SQLite, which frees it when the
+    /// database is closed. Unless `read_only` is `true`, SQLite is allowed to
+    /// grow the buffer (with `sqlite3_realloc64`) as the database is written
+    /// to.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `schema` is `Temp` or is not an attached database,
+    /// or if the database cannot be reopened.
+    pub fn deserialize(
+        &mut self,
+        schema: DatabaseName<'_>,
+        data: OwnedData,
+        read_only: bool,
+    ) -> Result<()> {
+        let name = schema.to_cstring()?;
+        // cached statements would reference the old database.
+        self.flush_prepared_statement_cache();
+        let flags = if read_only {
+            ffi::SQLITE_DESERIALIZE_FREEONCLOSE | ffi::SQLITE_DESERIALIZE_READONLY
+        } else {
+            ffi::SQLITE_DESERIALIZE_FREEONCLOSE | ffi::SQLITE_DESERIALIZE_RESIZEABLE
+        };
+        let (ptr, sz) = data.into_raw();
+        let mut c = self.db.borrow_mut();
+        // On failure, SQLite frees the buffer itself because of FREEONCLOSE.
+        let rc = unsafe {
+            ffi::sqlite3_deserialize(
+                c.db(),
+                name.as_ptr(),
+                ptr,
+                sz as ffi::sqlite3_int64,
+                sz as ffi::sqlite3_int64,
+                flags as c_uint,
+            )
+        };
+        c.decode_result(rc)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::OwnedData;
+    use crate::{Connection, DatabaseName, NO_PARAMS};
+
+    #[test]
+    fn test_serialize_deserialize() {
+        let src = Connection::open_in_memory().unwrap();
+        src.execute_batch("CREATE TABLE foo(x INTEGER); INSERT INTO foo VALUES (42);")
+            .unwrap();
+        let data = src.serialize(DatabaseName::Main).unwrap();
+        assert!(!data.is_empty());
+
+        let mut dst = Connection::open_in_memory().unwrap();
+        dst.deserialize(DatabaseName::Main, data, false).unwrap();
+        let x: i64 = dst
+            .query_row("SELECT x FROM foo", NO_PARAMS, |r| r.get(0))
+            .unwrap();
+        assert_eq!(42, x);
+
+        // the buffer is resizeable
+        dst.execute_batch("INSERT INTO foo SELECT x + 1 FROM foo; INSERT INTO foo SELECT x + 2 FROM foo; CREATE TABLE bar(y TEXT);")
+            .unwrap();
+        let count: i64 = dst
+            .query_row("SELECT count(*) FROM foo", NO_PARAMS, |r| r.get(0))
+            .unwrap();
+        assert_eq!(4, count);
+    }
+
+    #[test]
+    fn test_deserialize_read_only() {
+        let src = Connection::open_in_memory().unwrap();
+        src.execute_batch("CREATE TABLE foo(x INTEGER);").unwrap();
+        let data = OwnedData::from_slice(&src.serialize(DatabaseName::Main).unwrap()).unwrap();
+
+        let mut dst = Connection::open_in_memory().unwrap();
+        dst.deserialize(DatabaseName::Main, data, true).unwrap();
+        assert!(dst
+            .execute("INSERT INTO foo VALUES (1)", NO_PARAMS)
+            .is_err());
+    }
+
+    #[test]
+    fn test_serialize_empty_and_unknown() {
+        let db = Connection::open_in_memory().unwrap();
+        let data = db.serialize(DatabaseName::Main).unwrap();
+        assert!(data.is_empty());
+        db.serialize(DatabaseName::Attached("nope")).unwrap_err();
+    }
+}
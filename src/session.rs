@@ -1,10 +1,13 @@
 //! [Session Extension](https://sqlite.org/sessionintro.html)
 #![allow(non_camel_case_types)]
 
+use std::convert::TryInto;
 use std::ffi::CStr;
 use std::io::{Read, Write};
 use std::marker::PhantomData;
 use std::mem::MaybeUninit;
+#[cfg(feature = "changegroup_schema")]
+use std::ops::{Deref, DerefMut};
 use std::os::raw::{c_char, c_int, c_uchar, c_void};
 use std::panic::{catch_unwind, RefUnwindSafe};
 use std::ptr;
@@ -15,7 +18,7 @@ use fallible_streaming_iterator::FallibleStreamingIterator;
 use crate::error::error_from_sqlite_code;
 use crate::ffi;
 use crate::hooks::Action;
-use crate::types::ValueRef;
+use crate::types::{Value, ValueRef};
 use crate::{errmsg_to_string, str_to_cstring, Connection, DatabaseName, Result};
 
 // https://sqlite.org/session.html
@@ -200,6 +203,62 @@ impl Session<'_> {
             ffi::sqlite3session_indirect(self.s, if indirect { 1 } else { 0 });
         }
     }
+
+    /// Query the number of bytes of heap memory used by the session
+    #[cfg(feature = "modern_session")] // 3.36.0
+    pub fn memory_used(&self) -> i64 {
+        unsafe { ffi::sqlite3session_memory_used(self.s) }
+    }
+
+    /// Query an upper limit on the size of the changeset generated by
+    /// `changeset`
+    ///
+    /// Always 0 unless `SQLITE_SESSION_OBJCONFIG_SIZE` is enabled.
+    #[cfg(feature = "modern_session")] // 3.36.0
+    pub fn changeset_size(&self) -> i64 {
+        unsafe { ffi::sqlite3session_changeset_size(self.s) }
+    }
+
+    /// Query the current value of a session `config`
+    #[cfg(feature = "modern_session")] // 3.36.0
+    pub fn object_config(&self, config: SessionObjConfig) -> Result<bool> {
+        let mut val: c_int = -1;
+        check!(unsafe {
+            ffi::sqlite3session_object_config(
+                self.s,
+                config as c_int,
+                &mut val as *mut c_int as *mut c_void,
+            )
+        });
+        Ok(val != 0)
+    }
+
+    /// Change a session `config` and return its new value
+    ///
+    /// Must be called before any table is attached.
+    #[cfg(feature = "modern_session")] // 3.36.0
+    pub fn set_object_config(&mut self, config: SessionObjConfig, new_val: bool) -> Result<bool> {
+        let mut val: c_int = if new_val { 1 } else { 0 };
+        check!(unsafe {
+            ffi::sqlite3session_object_config(
+                self.s,
+                config as c_int,
+                &mut val as *mut c_int as *mut c_void,
+            )
+        });
+        Ok(val != 0)
+    }
+}
+
+/// Session Configuration Options
+#[cfg(feature = "modern_session")] // 3.36.0
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SessionObjConfig {
+    /// Whether `Session::changeset_size` is tracked (disabled by default)
+    SQLITE_SESSION_OBJCONFIG_SIZE = 1,
+    /// Whether tables without a primary key are recorded, using their rowid
+    SQLITE_SESSION_OBJCONFIG_ROWID = 2, // 3.42.0
 }
 
 impl Drop for Session<'_> {
@@ -226,6 +285,27 @@ pub fn invert_strm(input: &mut dyn Read, output: &mut dyn Write) -> Result<()> {
     Ok(())
 }
 
+/// Keep the changes of `cs` for which `filter` returns `true`
+///
+/// The result is a patchset if `cs` is one.
+pub fn changeset_filter<F>(cs: &Changeset, mut filter: F) -> Result<Changeset>
+where
+    F: FnMut(&ChangeRecord) -> bool,
+{
+    let mut encoder = if cs.is_patchset() {
+        ChangesetEncoder::patchset()
+    } else {
+        ChangesetEncoder::changeset()
+    };
+    for record in cs.records()? {
+        let record = record?;
+        if filter(&record) {
+            encoder.push(&record)?;
+        }
+    }
+    encoder.finish()
+}
+
 /// Combine two changesets
 pub fn concat_strm(
     input_a: &mut dyn Read,
@@ -276,6 +356,16 @@ impl Changeset {
         })
     }
 
+    /// Create an iterator decoding each change into an owned `ChangeRecord`
+    pub fn records(&self) -> Result<ChangeRecords<'_>> {
+        Ok(ChangeRecords { iter: self.iter()? })
+    }
+
+    // Each table header starts with 'P' in a patchset and 'T' otherwise.
+    fn is_patchset(&self) -> bool {
+        !self.cs.is_null() && self.n > 0 && unsafe { *(self.cs as *const u8) } == b'P'
+    }
+
     /// Concatenate two changeset objects
     pub fn concat(a: &Changeset, b: &Changeset) -> Result<Changeset> {
         let mut n = 0;
@@ -382,7 +472,6 @@ impl Drop for ChangesetIter<'_> {
 
 /// An item passed to a conflict-handler by `Connection::apply`,
 /// or an item generated by `ChangesetIter::next`.
-// TODO enum ? Delete, Insert, Update, ...
 pub struct ChangesetItem {
     it: *mut ffi::sqlite3_changeset_iter,
 }
@@ -420,37 +509,70 @@ impl ChangesetItem {
     /// Obtain new.* Values
     ///
     /// May only be called if the type of change is either `SQLITE_UPDATE` or
-    /// `SQLITE_INSERT`.
+    /// `SQLITE_INSERT`. `ValueRef::Null` is returned for a column left
+    /// unchanged by an `SQLITE_UPDATE`.
     pub fn new_value(&self, col: usize) -> Result<ValueRef<'_>> {
-        unsafe {
-            let mut p_value = MaybeUninit::uninit();
-            check!(ffi::sqlite3changeset_new(
-                self.it,
-                col as i32,
-                p_value.as_mut_ptr()
-            ));
-            let p_value: *mut ffi::sqlite3_value = p_value.assume_init();
-            Ok(ValueRef::from_value(p_value))
-        }
+        self.value(ffi::sqlite3changeset_new, col)
+            .map(|v| v.unwrap_or(ValueRef::Null))
     }
 
     /// Obtain old.* Values
     ///
     /// May only be called if the type of change is either `SQLITE_DELETE` or
-    /// `SQLITE_UPDATE`.
+    /// `SQLITE_UPDATE`. `ValueRef::Null` is returned for a column which is
+    /// not part of the change.
     pub fn old_value(&self, col: usize) -> Result<ValueRef<'_>> {
+        self.value(ffi::sqlite3changeset_old, col)
+            .map(|v| v.unwrap_or(ValueRef::Null))
+    }
+
+    // `None` when the value is undefined, e.g. an unchanged column in an
+    // `SQLITE_UPDATE`.
+    fn value(&self, f: ValueFn, col: usize) -> Result<Option<ValueRef<'_>>> {
         unsafe {
             let mut p_value = MaybeUninit::uninit();
-            check!(ffi::sqlite3changeset_old(
-                self.it,
-                col as i32,
-                p_value.as_mut_ptr()
-            ));
+            check!(f(self.it, col as i32, p_value.as_mut_ptr()));
             let p_value: *mut ffi::sqlite3_value = p_value.assume_init();
-            Ok(ValueRef::from_value(p_value))
+            if p_value.is_null() {
+                Ok(None)
+            } else {
+                Ok(Some(ValueRef::from_value(p_value)))
+            }
         }
     }
 
+    /// Decode the current change into an owned `ChangeRecord`
+    pub fn record(&self) -> Result<ChangeRecord> {
+        let op = self.op()?;
+        let n = op.number_of_columns() as usize;
+        let values = |f: ValueFn| -> Result<Vec<Option<Value>>> {
+            (0..n)
+                .map(|col| self.value(f, col)?.map(owned_value).transpose())
+                .collect()
+        };
+        let change = match op.code() {
+            Action::SQLITE_INSERT => ChangeOp::Insert {
+                new: (0..n)
+                    .map(|col| self.new_value(col).and_then(owned_value))
+                    .collect::<Result<_>>()?,
+            },
+            Action::SQLITE_UPDATE => ChangeOp::Update {
+                old: values(ffi::sqlite3changeset_old)?,
+                new: values(ffi::sqlite3changeset_new)?,
+            },
+            Action::SQLITE_DELETE => ChangeOp::Delete {
+                old: values(ffi::sqlite3changeset_old)?,
+            },
+            Action::UNKNOWN => return Err(error_from_sqlite_code(ffi::SQLITE_CORRUPT, None)),
+        };
+        Ok(ChangeRecord {
+            table: op.table_name().to_owned(),
+            pk_mask: self.pk()?.to_vec(),
+            op: change,
+            indirect: op.indirect(),
+        })
+    }
+
     /// Obtain the current operation
     pub fn op(&self) -> Result<Operation<'_>> {
         let mut number_of_columns = 0;
@@ -493,6 +615,257 @@ impl ChangesetItem {
     }
 }
 
+/// Like `Value::from`, but without panicking on invalid UTF-8 text, which
+/// SQLite does not reject.
+fn owned_value(value: ValueRef<'_>) -> Result<Value> {
+    Ok(match value {
+        ValueRef::Text(s) => Value::Text(std::str::from_utf8(s)?.to_owned()),
+        value => Value::from(value),
+    })
+}
+
+type ValueFn = unsafe extern "C" fn(
+    *mut ffi::sqlite3_changeset_iter,
+    c_int,
+    *mut *mut ffi::sqlite3_value,
+) -> c_int;
+
+/// A change to a single row, decoded from a changeset or patchset.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChangeRecord {
+    /// Name of the changed table
+    pub table: String,
+    /// One entry per column, non-zero for the columns which are part of the
+    /// primary key
+    pub pk_mask: Vec<u8>,
+    /// The change itself
+    pub op: ChangeOp,
+    /// Whether the change was made indirectly, e.g. by a trigger
+    pub indirect: bool,
+}
+
+/// Row values of a `ChangeRecord`, one entry per column.
+///
+/// `None` stands for a value which is not part of the change: a column left
+/// unchanged by an update, or a column outside the primary key of a row
+/// deleted by a patchset.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChangeOp {
+    /// A row was inserted
+    Insert { new: Vec<Value> },
+    /// A row was updated. For patchsets, `old` only holds the primary key.
+    Update {
+        old: Vec<Option<Value>>,
+        new: Vec<Option<Value>>,
+    },
+    /// A row was deleted
+    Delete { old: Vec<Option<Value>> },
+}
+
+/// Iterator over the decoded changes of a changeset, see
+/// `Changeset::records`.
+pub struct ChangeRecords<'changeset> {
+    iter: ChangesetIter<'changeset>,
+}
+
+impl Iterator for ChangeRecords<'_> {
+    type Item = Result<ChangeRecord>;
+
+    fn next(&mut self) -> Option<Result<ChangeRecord>> {
+        match self.iter.next() {
+            Ok(Some(item)) => Some(item.record()),
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+/// Writes `ChangeRecord`s into a changeset or patchset.
+///
+/// Consecutive records for the same table share a table header, so records
+/// should be grouped by table.
+pub struct ChangesetEncoder {
+    buf: Vec<u8>,
+    patchset: bool,
+    table: Option<(String, Vec<u8>)>,
+}
+
+impl ChangesetEncoder {
+    /// Create an encoder producing a changeset
+    pub fn changeset() -> ChangesetEncoder {
+        ChangesetEncoder {
+            buf: Vec::new(),
+            patchset: false,
+            table: None,
+        }
+    }
+
+    /// Create an encoder producing a patchset
+    ///
+    /// Patchsets only keep the primary key of deleted rows and the new values
+    /// of updated rows, so any other old value is dropped.
+    pub fn patchset() -> ChangesetEncoder {
+        ChangesetEncoder {
+            patchset: true,
+            ..ChangesetEncoder::changeset()
+        }
+    }
+
+    /// Append `record`
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the number of values does not match `pk_mask`,
+    /// or if a value required by the format is `None`.
+    pub fn push(&mut self, record: &ChangeRecord) -> Result<()> {
+        let n = record.pk_mask.len();
+        let is_pk = |col: usize| record.pk_mask[col] != 0;
+        let misuse = |msg: &str| {
+            Err(error_from_sqlite_code(
+                ffi::SQLITE_MISUSE,
+                Some(format!("{} for table {}", msg, record.table)),
+            ))
+        };
+        let counts_match = match record.op {
+            ChangeOp::Insert { ref new } => new.len() == n,
+            ChangeOp::Update { ref old, ref new } => old.len() == n && new.len() == n,
+            ChangeOp::Delete { ref old } => old.len() == n,
+        };
+        if !counts_match {
+            return misuse("wrong number of values");
+        }
+        let missing = match record.op {
+            ChangeOp::Insert { .. } => false,
+            ChangeOp::Update { ref old, .. } => (0..n).any(|col| is_pk(col) && old[col].is_none()),
+            ChangeOp::Delete { ref old } => {
+                (0..n).any(|col| (!self.patchset || is_pk(col)) && old[col].is_none())
+            }
+        };
+        if missing {
+            return misuse("missing old value");
+        }
+
+        let same_table = match self.table {
+            Some((ref table, ref pk_mask)) => *table == record.table && *pk_mask == record.pk_mask,
+            None => false,
+        };
+        if !same_table {
+            self.push_table_header(record)?;
+        }
+
+        let buf = &mut self.buf;
+        let (code, values): (c_int, Vec<Option<&Value>>) = match record.op {
+            ChangeOp::Insert { ref new } => (ffi::SQLITE_INSERT, new.iter().map(Some).collect()),
+            ChangeOp::Update { ref old, ref new } if self.patchset => (
+                ffi::SQLITE_UPDATE,
+                (0..n)
+                    .map(|col| if is_pk(col) { &old[col] } else { &new[col] }.as_ref())
+                    .collect(),
+            ),
+            ChangeOp::Update { ref old, ref new } => (
+                ffi::SQLITE_UPDATE,
+                old.iter().chain(new.iter()).map(Option::as_ref).collect(),
+            ),
+            ChangeOp::Delete { ref old } if self.patchset => (
+                ffi::SQLITE_DELETE,
+                (0..n)
+                    .filter(|&col| is_pk(col))
+                    .map(|col| old[col].as_ref())
+                    .collect(),
+            ),
+            ChangeOp::Delete { ref old } => {
+                (ffi::SQLITE_DELETE, old.iter().map(Option::as_ref).collect())
+            }
+        };
+        buf.push(code as u8);
+        buf.push(record.indirect as u8);
+        for value in values {
+            push_value(buf, value);
+        }
+        Ok(())
+    }
+
+    fn push_table_header(&mut self, record: &ChangeRecord) -> Result<()> {
+        if record.table.contains('\0') {
+            return Err(error_from_sqlite_code(
+                ffi::SQLITE_MISUSE,
+                Some(format!("invalid table name {:?}", record.table)),
+            ));
+        }
+        self.buf.push(if self.patchset { b'P' } else { b'T' });
+        push_varint(&mut self.buf, record.pk_mask.len());
+        self.buf.extend_from_slice(&record.pk_mask);
+        self.buf.extend_from_slice(record.table.as_bytes());
+        self.buf.push(0);
+        self.table = Some((record.table.clone(), record.pk_mask.clone()));
+        Ok(())
+    }
+
+    /// Returns the encoded bytes, e.g. for `Connection::apply_strm`
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+
+    /// Returns the encoded `Changeset`
+    pub fn finish(self) -> Result<Changeset> {
+        let n = self.buf.len();
+        let n_c: c_int = n
+            .try_into()
+            .map_err(|_| error_from_sqlite_code(ffi::SQLITE_TOOBIG, None))?;
+        if n == 0 {
+            return Ok(Changeset {
+                cs: ptr::null_mut(),
+                n: 0,
+            });
+        }
+        let cs = unsafe { ffi::sqlite3_malloc(n_c) };
+        if cs.is_null() {
+            return Err(error_from_sqlite_code(ffi::SQLITE_NOMEM, None));
+        }
+        unsafe { ptr::copy_nonoverlapping(self.buf.as_ptr(), cs as *mut u8, n) };
+        Ok(Changeset { cs, n: n_c })
+    }
+}
+
+// Same encoding as `sqlite3PutVarint`, sizes never need the 9 bytes form.
+fn push_varint(buf: &mut Vec<u8>, mut v: usize) {
+    let mut bytes = Vec::with_capacity(5);
+    loop {
+        bytes.push((v & 0x7f) as u8 | 0x80);
+        v >>= 7;
+        if v == 0 {
+            break;
+        }
+    }
+    bytes[0] &= 0x7f;
+    buf.extend(bytes.iter().rev());
+}
+
+fn push_value(buf: &mut Vec<u8>, value: Option<&Value>) {
+    match value {
+        None => buf.push(0),
+        Some(Value::Null) => buf.push(ffi::SQLITE_NULL as u8),
+        Some(Value::Integer(i)) => {
+            buf.push(ffi::SQLITE_INTEGER as u8);
+            buf.extend_from_slice(&i.to_be_bytes());
+        }
+        Some(Value::Real(f)) => {
+            buf.push(ffi::SQLITE_FLOAT as u8);
+            buf.extend_from_slice(&f.to_bits().to_be_bytes());
+        }
+        Some(Value::Text(s)) => {
+            buf.push(ffi::SQLITE_TEXT as u8);
+            push_varint(buf, s.len());
+            buf.extend_from_slice(s.as_bytes());
+        }
+        Some(Value::Blob(b)) => {
+            buf.push(ffi::SQLITE_BLOB as u8);
+            push_varint(buf, b.len());
+            buf.extend_from_slice(b);
+        }
+    }
+}
+
 /// Used to combine two or more changesets or
 /// patchsets
 pub struct Changegroup {
@@ -507,6 +880,30 @@ impl Changegroup {
         Ok(Changegroup { cg })
     }
 
+    /// Use the schema of the `name` database of `db` for the tables of the
+    /// changesets added afterwards.
+    ///
+    /// Changes with fewer columns than the table, recorded before columns
+    /// were added with `ALTER TABLE ... ADD COLUMN`, are then completed with
+    /// the default values of the missing columns, and changes for a table
+    /// whose primary key differs are rejected.
+    ///
+    /// Must be called before any changeset is added.
+    #[cfg(feature = "changegroup_schema")] // 3.44.0
+    pub fn schema<'conn>(
+        self,
+        db: &'conn Connection,
+        name: DatabaseName<'_>,
+    ) -> Result<SchemaChangegroup<'conn>> {
+        let name = name.to_cstring()?;
+        let c = db.db.borrow_mut().db;
+        check!(unsafe { ffi::sqlite3changegroup_schema(self.cg, c, name.as_ptr()) });
+        Ok(SchemaChangegroup {
+            phantom: PhantomData,
+            group: self,
+        })
+    }
+
     /// Add a changeset
     pub fn add(&mut self, cs: &Changeset) -> Result<()> {
         check!(unsafe { ffi::sqlite3changegroup_add(self.cg, cs.n, cs.cs) });
@@ -557,6 +954,30 @@ impl Drop for Changegroup {
     }
 }
 
+/// A `Changegroup` using the schema of a database connection, see
+/// `Changegroup::schema`.
+#[cfg(feature = "changegroup_schema")] // 3.44.0
+pub struct SchemaChangegroup<'conn> {
+    phantom: PhantomData<&'conn Connection>,
+    group: Changegroup,
+}
+
+#[cfg(feature = "changegroup_schema")]
+impl Deref for SchemaChangegroup<'_> {
+    type Target = Changegroup;
+
+    fn deref(&self) -> &Changegroup {
+        &self.group
+    }
+}
+
+#[cfg(feature = "changegroup_schema")]
+impl DerefMut for SchemaChangegroup<'_> {
+    fn deref_mut(&mut self) -> &mut Changegroup {
+        &mut self.group
+    }
+}
+
 impl Connection {
     /// Apply a changeset to a database
     pub fn apply<F, C>(&self, cs: &Changeset, filter: Option<F>, conflict: C) -> Result<()>
@@ -633,6 +1054,194 @@ impl Connection {
     }
 }
 
+#[cfg(feature = "modern_session")] // 3.22.0
+impl Connection {
+    /// Apply a changeset to a database, as `apply` does, and return a rebase
+    /// buffer to configure a `Rebaser` with.
+    pub fn apply_v2<F, C>(
+        &self,
+        cs: &Changeset,
+        filter: Option<F>,
+        conflict: C,
+        flags: ApplyFlags,
+    ) -> Result<Vec<u8>>
+    where
+        F: Fn(&str) -> bool + Send + RefUnwindSafe + 'static,
+        C: Fn(ConflictType, ChangesetItem) -> ConflictAction + Send + RefUnwindSafe + 'static,
+    {
+        let db = self.db.borrow_mut().db;
+
+        let x_filter = filter_callback::<F, C>(&filter);
+        let tuple = &mut (filter, conflict);
+        let mut n_rebase = 0;
+        let mut p_rebase = ptr::null_mut();
+        let rc = unsafe {
+            ffi::sqlite3changeset_apply_v2(
+                db,
+                cs.n,
+                cs.cs,
+                x_filter,
+                Some(call_conflict::<F, C>),
+                tuple as *mut (Option<F>, C) as *mut c_void,
+                &mut p_rebase,
+                &mut n_rebase,
+                flags.bits(),
+            )
+        };
+        let rebase = unsafe { take_buffer(p_rebase, n_rebase) };
+        check!(rc);
+        Ok(rebase)
+    }
+
+    /// Apply a changeset read from `input` to a database, as `apply_strm`
+    /// does, and return a rebase buffer to configure a `Rebaser` with.
+    pub fn apply_v2_strm<F, C>(
+        &self,
+        input: &mut dyn Read,
+        filter: Option<F>,
+        conflict: C,
+        flags: ApplyFlags,
+    ) -> Result<Vec<u8>>
+    where
+        F: Fn(&str) -> bool + Send + RefUnwindSafe + 'static,
+        C: Fn(ConflictType, ChangesetItem) -> ConflictAction + Send + RefUnwindSafe + 'static,
+    {
+        let input_ref = &input;
+        let db = self.db.borrow_mut().db;
+
+        let x_filter = filter_callback::<F, C>(&filter);
+        let tuple = &mut (filter, conflict);
+        let mut n_rebase = 0;
+        let mut p_rebase = ptr::null_mut();
+        let rc = unsafe {
+            ffi::sqlite3changeset_apply_v2_strm(
+                db,
+                Some(x_input),
+                input_ref as *const &mut dyn Read as *mut c_void,
+                x_filter,
+                Some(call_conflict::<F, C>),
+                tuple as *mut (Option<F>, C) as *mut c_void,
+                &mut p_rebase,
+                &mut n_rebase,
+                flags.bits(),
+            )
+        };
+        let rebase = unsafe { take_buffer(p_rebase, n_rebase) };
+        check!(rc);
+        Ok(rebase)
+    }
+}
+
+#[cfg(feature = "modern_session")] // 3.22.0
+bitflags::bitflags! {
+    #[doc = "Flags for `Connection::apply_v2`."]
+    #[doc = "See [sqlite3changeset_apply_v2](https://sqlite.org/session/sqlite3changeset_apply.html) for details."]
+    #[repr(C)]
+    pub struct ApplyFlags: ::std::os::raw::c_int {
+        const SQLITE_CHANGESETAPPLY_NOSAVEPOINT = 0x0001; // 3.22.0
+        const SQLITE_CHANGESETAPPLY_INVERT      = 0x0002; // 3.25.0
+        /// Ignored by SQLite versions older than 3.41.0.
+        const SQLITE_CHANGESETAPPLY_IGNORENOOP  = 0x0004; // 3.41.0
+    }
+}
+
+#[cfg(feature = "modern_session")] // 3.22.0
+impl Default for ApplyFlags {
+    fn default() -> ApplyFlags {
+        ApplyFlags::empty()
+    }
+}
+
+/// Rebases changesets over the changes applied by `Connection::apply_v2`.
+#[cfg(feature = "modern_session")] // 3.25.0
+pub struct Rebaser {
+    r: *mut ffi::sqlite3_rebaser,
+}
+
+#[cfg(feature = "modern_session")] // 3.25.0
+impl Rebaser {
+    /// Create a new rebaser
+    pub fn new() -> Result<Rebaser> {
+        let mut r = MaybeUninit::uninit();
+        check!(unsafe { ffi::sqlite3rebaser_create(r.as_mut_ptr()) });
+        let r: *mut ffi::sqlite3_rebaser = unsafe { r.assume_init() };
+        Ok(Rebaser { r })
+    }
+
+    /// Add a rebase buffer returned by `Connection::apply_v2`
+    pub fn configure(&mut self, rebase: &[u8]) -> Result<()> {
+        let n: c_int = rebase
+            .len()
+            .try_into()
+            .map_err(|_| error_from_sqlite_code(ffi::SQLITE_TOOBIG, None))?;
+        check!(unsafe {
+            ffi::sqlite3rebaser_configure(self.r, n, rebase.as_ptr() as *const c_void)
+        });
+        Ok(())
+    }
+
+    /// Rebase a changeset
+    pub fn rebase(&mut self, cs: &Changeset) -> Result<Changeset> {
+        let mut n = 0;
+        let mut output = MaybeUninit::uninit();
+        check!(unsafe {
+            ffi::sqlite3rebaser_rebase(self.r, cs.n, cs.cs, &mut n, output.as_mut_ptr())
+        });
+        let output: *mut c_void = unsafe { output.assume_init() };
+        Ok(Changeset { cs: output, n })
+    }
+
+    /// Rebase a changeset read from `input` and write it to `output`.
+    pub fn rebase_strm(&mut self, input: &mut dyn Read, output: &mut dyn Write) -> Result<()> {
+        let input_ref = &input;
+        let output_ref = &output;
+        check!(unsafe {
+            ffi::sqlite3rebaser_rebase_strm(
+                self.r,
+                Some(x_input),
+                input_ref as *const &mut dyn Read as *mut c_void,
+                Some(x_output),
+                output_ref as *const &mut dyn Write as *mut c_void,
+            )
+        });
+        Ok(())
+    }
+}
+
+#[cfg(feature = "modern_session")] // 3.25.0
+impl Drop for Rebaser {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::sqlite3rebaser_delete(self.r);
+        }
+    }
+}
+
+#[cfg(feature = "modern_session")]
+unsafe fn take_buffer(p: *mut c_void, n: c_int) -> Vec<u8> {
+    if p.is_null() {
+        return Vec::new();
+    }
+    let buf = from_raw_parts(p as *const u8, n as usize).to_vec();
+    ffi::sqlite3_free(p);
+    buf
+}
+
+#[cfg(feature = "modern_session")]
+fn filter_callback<F, C>(
+    filter: &Option<F>,
+) -> Option<unsafe extern "C" fn(*mut c_void, *const c_char) -> c_int>
+where
+    F: Fn(&str) -> bool + Send + RefUnwindSafe + 'static,
+    C: Fn(ConflictType, ChangesetItem) -> ConflictAction + Send + RefUnwindSafe + 'static,
+{
+    if filter.is_some() {
+        Some(call_filter::<F, C>)
+    } else {
+        None
+    }
+}
+
 /// Constants passed to the conflict handler
 #[repr(i32)]
 #[derive(Debug, PartialEq)]
@@ -745,9 +1354,15 @@ mod test {
     use std::io::Read;
     use std::sync::atomic::{AtomicBool, Ordering};
 
-    use super::{Changeset, ChangesetIter, ConflictAction, ConflictType, Session};
+    use super::{
+        changeset_filter, ChangeOp, ChangeRecord, Changeset, ChangesetEncoder, ChangesetIter,
+        ConflictAction, ConflictType, Session,
+    };
+    #[cfg(feature = "modern_session")]
+    use super::{ApplyFlags, Rebaser, SessionObjConfig};
     use crate::hooks::Action;
-    use crate::Connection;
+    use crate::types::Value;
+    use crate::{Connection, NO_PARAMS};
 
     fn one_changeset() -> Changeset {
         let db = Connection::open_in_memory().unwrap();
@@ -918,4 +1533,404 @@ mod test {
         session.set_indirect(true);
         assert!(session.is_indirect());
     }
+
+    fn bytes(cs: &Changeset) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(cs.cs as *const u8, cs.n as usize) }
+    }
+
+    fn various_changes(patchset: bool) -> Changeset {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(
+            "CREATE TABLE foo(a INTEGER, b TEXT, c REAL, d BLOB, PRIMARY KEY (b, a));
+             INSERT INTO foo VALUES (1, 'one', 1.5, x'01');
+             INSERT INTO foo VALUES (2, 'two', NULL, x'02');",
+        )
+        .unwrap();
+        let mut session = Session::new(&db).unwrap();
+        session.attach(None).unwrap();
+        db.execute_batch(
+            "INSERT INTO foo VALUES (3, 'three', -0.25, zeroblob(200));
+             UPDATE foo SET c = 2.5 WHERE a = 1;
+             DELETE FROM foo WHERE a = 2;",
+        )
+        .unwrap();
+        if patchset {
+            session.patchset().unwrap()
+        } else {
+            session.changeset().unwrap()
+        }
+    }
+
+    #[test]
+    fn test_changeset_records() {
+        let changeset = various_changes(false);
+        let mut records = changeset
+            .records()
+            .unwrap()
+            .collect::<crate::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(3, records.len());
+        records.sort_by_key(|r| match r.op {
+            ChangeOp::Insert { .. } => 0,
+            ChangeOp::Update { .. } => 1,
+            ChangeOp::Delete { .. } => 2,
+        });
+        assert_eq!(
+            ChangeRecord {
+                table: "foo".to_owned(),
+                pk_mask: vec![2, 1, 0, 0],
+                op: ChangeOp::Insert {
+                    new: vec![
+                        Value::Integer(3),
+                        Value::Text("three".to_owned()),
+                        Value::Real(-0.25),
+                        Value::Blob(vec![0; 200]),
+                    ]
+                },
+                indirect: false,
+            },
+            records[0]
+        );
+        assert_eq!(
+            ChangeOp::Update {
+                old: vec![
+                    Some(Value::Integer(1)),
+                    Some(Value::Text("one".to_owned())),
+                    Some(Value::Real(1.5)),
+                    None,
+                ],
+                new: vec![None, None, Some(Value::Real(2.5)), None],
+            },
+            records[1].op
+        );
+        assert_eq!(
+            ChangeOp::Delete {
+                old: vec![
+                    Some(Value::Integer(2)),
+                    Some(Value::Text("two".to_owned())),
+                    Some(Value::Null),
+                    Some(Value::Blob(vec![2])),
+                ]
+            },
+            records[2].op
+        );
+    }
+
+    #[test]
+    fn test_changeset_records_invalid_utf8() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch("CREATE TABLE foo(t TEXT PRIMARY KEY NOT NULL);")
+            .unwrap();
+        let mut session = Session::new(&db).unwrap();
+        session.attach(None).unwrap();
+        db.execute_batch("INSERT INTO foo (t) VALUES (CAST(X'FF' AS TEXT));")
+            .unwrap();
+        let changeset = session.changeset().unwrap();
+        let mut records = changeset.records().unwrap();
+        match records.next().unwrap() {
+            Err(crate::Error::Utf8Error(_)) => (),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_changeset_encoder_round_trip() {
+        for &patchset in &[false, true] {
+            let changeset = various_changes(patchset);
+            let mut encoder = if patchset {
+                ChangesetEncoder::patchset()
+            } else {
+                ChangesetEncoder::changeset()
+            };
+            for record in changeset.records().unwrap() {
+                encoder.push(&record.unwrap()).unwrap();
+            }
+            let encoded = encoder.finish().unwrap();
+            assert_eq!(bytes(&changeset), bytes(&encoded));
+        }
+    }
+
+    #[test]
+    fn test_changeset_encoder_apply() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch("CREATE TABLE foo(t TEXT PRIMARY KEY NOT NULL, n INTEGER);")
+            .unwrap();
+
+        // rewrite every inserted row before applying the changes
+        let mut encoder = ChangesetEncoder::changeset();
+        let changeset = one_changeset();
+        for record in changeset.records().unwrap() {
+            let mut record = record.unwrap();
+            record.pk_mask.push(0);
+            if let ChangeOp::Insert { ref mut new } = record.op {
+                new.push(Value::Integer(42));
+            }
+            encoder.push(&record).unwrap();
+        }
+        let input = encoder.into_bytes();
+        db.apply_strm(
+            &mut input.as_slice(),
+            None::<fn(&str) -> bool>,
+            |_conflict_type, _item| ConflictAction::SQLITE_CHANGESET_ABORT,
+        )
+        .unwrap();
+        let n: i64 = db
+            .query_row("SELECT n FROM foo WHERE t = 'bar'", NO_PARAMS, |r| r.get(0))
+            .unwrap();
+        assert_eq!(42, n);
+
+        let mut encoder = ChangesetEncoder::changeset();
+        let bad = ChangeRecord {
+            table: "foo".to_owned(),
+            pk_mask: vec![1, 0],
+            op: ChangeOp::Delete {
+                old: vec![Some(Value::Text("bar".to_owned())), None],
+            },
+            indirect: false,
+        };
+        assert!(encoder.push(&bad).is_err());
+    }
+
+    #[cfg(feature = "modern_session")]
+    fn update_changeset(db: &Connection, v: &str) -> Changeset {
+        let mut session = Session::new(db).unwrap();
+        session.attach(None).unwrap();
+        db.execute("UPDATE foo SET v = ? WHERE id = 1", &[v])
+            .unwrap();
+        session.changeset().unwrap()
+    }
+
+    #[cfg(feature = "modern_session")]
+    fn value(db: &Connection) -> String {
+        db.query_row("SELECT v FROM foo WHERE id = 1", NO_PARAMS, |r| r.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    #[cfg(feature = "modern_session")]
+    fn test_rebaser() {
+        let schema = "CREATE TABLE foo(id INTEGER PRIMARY KEY, v TEXT);
+                      INSERT INTO foo VALUES (1, 'base');";
+        let local = Connection::open_in_memory().unwrap();
+        local.execute_batch(schema).unwrap();
+        let remote = Connection::open_in_memory().unwrap();
+        remote.execute_batch(schema).unwrap();
+
+        let local_cs = update_changeset(&local, "local");
+        let remote_cs = update_changeset(&remote, "remote");
+
+        // keep the local change
+        let rebase = local
+            .apply_v2(
+                &remote_cs,
+                None::<fn(&str) -> bool>,
+                |conflict_type, _item| {
+                    assert_eq!(ConflictType::SQLITE_CHANGESET_DATA, conflict_type);
+                    ConflictAction::SQLITE_CHANGESET_OMIT
+                },
+                ApplyFlags::default(),
+            )
+            .unwrap();
+        assert!(!rebase.is_empty());
+        assert_eq!("local", value(&local));
+
+        let mut rebaser = Rebaser::new().unwrap();
+        rebaser.configure(&rebase).unwrap();
+        let rebased = rebaser.rebase(&local_cs).unwrap();
+
+        // the rebased change no longer conflicts with the remote one
+        remote
+            .apply(
+                &rebased,
+                None::<fn(&str) -> bool>,
+                |_conflict_type, _item| ConflictAction::SQLITE_CHANGESET_ABORT,
+            )
+            .unwrap();
+        assert_eq!("local", value(&remote));
+
+        let mut output = Vec::new();
+        let mut input = bytes(&local_cs);
+        rebaser.rebase_strm(&mut input, &mut output).unwrap();
+        assert_eq!(bytes(&rebased), output.as_slice());
+    }
+
+    #[test]
+    #[cfg(feature = "modern_session")]
+    fn test_apply_v2_invert() {
+        let changeset = one_changeset();
+
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(
+            "CREATE TABLE foo(t TEXT PRIMARY KEY NOT NULL);
+             INSERT INTO foo VALUES ('bar');",
+        )
+        .unwrap();
+        db.apply_v2(
+            &changeset,
+            None::<fn(&str) -> bool>,
+            |_conflict_type, _item| ConflictAction::SQLITE_CHANGESET_ABORT,
+            ApplyFlags::SQLITE_CHANGESETAPPLY_INVERT
+                | ApplyFlags::SQLITE_CHANGESETAPPLY_NOSAVEPOINT,
+        )
+        .unwrap();
+        let count: i64 = db
+            .query_row("SELECT count(*) FROM foo", NO_PARAMS, |r| r.get(0))
+            .unwrap();
+        assert_eq!(0, count);
+
+        let output = one_changeset_strm();
+        db.apply_v2_strm(
+            &mut output.as_slice(),
+            Some(|table: &str| table == "foo"),
+            |_conflict_type, _item| ConflictAction::SQLITE_CHANGESET_ABORT,
+            ApplyFlags::default(),
+        )
+        .unwrap();
+        let count: i64 = db
+            .query_row("SELECT count(*) FROM foo", NO_PARAMS, |r| r.get(0))
+            .unwrap();
+        assert_eq!(1, count);
+    }
+
+    #[test]
+    #[cfg(feature = "modern_session")]
+    fn test_session_memory_and_size() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch("CREATE TABLE foo(t TEXT PRIMARY KEY NOT NULL);")
+            .unwrap();
+
+        let mut session = Session::new(&db).unwrap();
+        assert!(!session
+            .object_config(SessionObjConfig::SQLITE_SESSION_OBJCONFIG_SIZE)
+            .unwrap());
+        assert!(session
+            .set_object_config(SessionObjConfig::SQLITE_SESSION_OBJCONFIG_SIZE, true)
+            .unwrap());
+        session.attach(None).unwrap();
+        db.execute("INSERT INTO foo (t) VALUES (?);", &["bar"])
+            .unwrap();
+
+        assert!(session.memory_used() > 0);
+        let size = session.changeset_size();
+        let changeset = session.changeset().unwrap();
+        assert!(size >= i64::from(changeset.n));
+
+        let mut session = Session::new(&db).unwrap();
+        session.attach(None).unwrap();
+        db.execute("INSERT INTO foo (t) VALUES (?);", &["baz"])
+            .unwrap();
+        assert_eq!(0, session.changeset_size());
+    }
+
+    #[test]
+    fn test_changeset_filter() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(
+            "CREATE TABLE public(id INTEGER PRIMARY KEY, v TEXT);
+             CREATE TABLE private(id INTEGER PRIMARY KEY, v TEXT);",
+        )
+        .unwrap();
+        let mut session = Session::new(&db).unwrap();
+        session.attach(None).unwrap();
+        db.execute_batch(
+            "INSERT INTO public VALUES (1, 'a'), (2, 'b');
+             INSERT INTO private VALUES (1, 'secret');",
+        )
+        .unwrap();
+
+        for &patchset in &[false, true] {
+            let cs = if patchset {
+                session.patchset().unwrap()
+            } else {
+                session.changeset().unwrap()
+            };
+            assert_eq!(patchset, cs.is_patchset());
+            let filtered = changeset_filter(&cs, |record| {
+                record.table != "private"
+                    && record.op
+                        != ChangeOp::Insert {
+                            new: vec![Value::Integer(2), Value::Text("b".to_owned())],
+                        }
+            })
+            .unwrap();
+            assert_eq!(patchset, filtered.is_patchset());
+            let records = filtered
+                .records()
+                .unwrap()
+                .collect::<crate::Result<Vec<_>>>()
+                .unwrap();
+            assert_eq!(1, records.len());
+            assert_eq!("public", records[0].table);
+        }
+
+        let empty = changeset_filter(&session.changeset().unwrap(), |_| false).unwrap();
+        assert_eq!(0, empty.n);
+        assert_eq!(0, empty.records().unwrap().count());
+    }
+
+    #[test]
+    #[cfg(feature = "changegroup_schema")]
+    fn test_changegroup_schema() {
+        use super::Changegroup;
+        use crate::DatabaseName;
+
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch("CREATE TABLE foo(id INTEGER PRIMARY KEY, a TEXT);")
+            .unwrap();
+        let mut session = Session::new(&db).unwrap();
+        session.attach(None).unwrap();
+        db.execute_batch("INSERT INTO foo VALUES (1, 'one');")
+            .unwrap();
+        let before = session.changeset().unwrap();
+        drop(session);
+
+        db.execute_batch("ALTER TABLE foo ADD COLUMN b INTEGER DEFAULT 42;")
+            .unwrap();
+        let mut session = Session::new(&db).unwrap();
+        session.attach(None).unwrap();
+        db.execute_batch("INSERT INTO foo VALUES (2, 'two', 7);")
+            .unwrap();
+        let after = session.changeset().unwrap();
+
+        // without the schema, changes with different column counts conflict
+        let mut group = Changegroup::new().unwrap();
+        group.add(&before).unwrap();
+        group.add(&after).unwrap_err();
+
+        let mut group = Changegroup::new()
+            .unwrap()
+            .schema(&db, DatabaseName::Main)
+            .unwrap();
+        group.add(&before).unwrap();
+        group.add(&after).unwrap();
+        let mut records = group
+            .output()
+            .unwrap()
+            .records()
+            .unwrap()
+            .map(|r| match r.unwrap().op {
+                ChangeOp::Insert { new } => new,
+                op => panic!("unexpected change {:?}", op),
+            })
+            .collect::<Vec<_>>();
+        records.sort_by_key(|new| match new[0] {
+            Value::Integer(id) => id,
+            _ => 0,
+        });
+        assert_eq!(
+            vec![
+                vec![
+                    Value::Integer(1),
+                    Value::Text("one".to_owned()),
+                    Value::Integer(42)
+                ],
+                vec![
+                    Value::Integer(2),
+                    Value::Text("two".to_owned()),
+                    Value::Integer(7)
+                ],
+            ],
+            records
+        );
+    }
 }
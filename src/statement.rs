@@ -3,17 +3,31 @@ use std::os::raw::{c_int, c_void};
 #[cfg(feature = "array")]
 use std::rc::Rc;
 use std::slice::from_raw_parts;
+#[cfg(feature = "hooks")]
+use std::time::Instant;
 use std::{convert, fmt, mem, ptr, result, str};
 
 use super::ffi;
 use super::{len_as_c_int, str_for_sqlite, str_to_cstring};
 use super::{
-    AndThenRows, Connection, Error, MappedRows, RawStatement, Result, Row, Rows, ValueRef,
+    AndThenRows, Connection, Error, FromRow, MappedRows, RawStatement, Result, Row, Rows, ValueRef,
 };
 use crate::types::{ToSql, ToSqlOutput};
 #[cfg(feature = "array")]
 use crate::vtab::array::{free_array, ARRAY_TYPE};
 
+/// A type whose fields can be bound as parameters of a statement.
+///
+/// With the `derive` feature, it can be implemented with
+/// `#[derive(ToParams)]`: fields are bound to the parameters named `:field`,
+/// `@field` or `$field` (or by position for tuple structs), and the
+/// `#[rusqlite(rename = "name")]` and `#[rusqlite(skip)]` attributes are
+/// supported.
+pub trait ToParams {
+    /// Bind `self` to the parameters of `stmt`.
+    fn bind_params(&self, stmt: &mut Statement<'_>) -> Result<()>;
+}
+
 /// A prepared statement.
 pub struct Statement<'conn> {
     conn: &'conn Connection,
@@ -94,6 +108,21 @@ impl Statement<'_> {
         self.execute_with_bound_parameters()
     }
 
+    /// Execute the prepared statement with parameters bound by `params`.
+    ///
+    /// On success, returns the number of rows that were changed or inserted
+    /// or deleted (via `sqlite3_changes`).
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if binding parameters fails, the executed statement
+    /// returns rows (in which case `query_params` should be used instead), or
+    /// the underlying SQLite call fails.
+    pub fn execute_params<T: ToParams + ?Sized>(&mut self, params: &T) -> Result<usize> {
+        params.bind_params(self)?;
+        self.execute_with_bound_parameters()
+    }
+
     /// Execute an INSERT and return the ROWID.
     ///
     /// # Note
@@ -156,6 +185,70 @@ impl Statement<'_> {
         Ok(Rows::new(self))
     }
 
+    /// Execute the prepared statement, interrupting it if it is still running
+    /// at `deadline`, and pass the resulting rows to `f`.
+    ///
+    /// The deadline is checked every few virtual machine instructions by a
+    /// progress handler (see `Connection::progress_handler`). Once it has
+    /// passed, stepping through the rows fails with
+    /// `Error::ProgressHandlerInterrupted`. Any progress handler previously
+    /// registered on the connection is suspended while `f` runs and restored
+    /// afterwards.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,no_run
+    /// # use rusqlite::{Connection, Result, NO_PARAMS};
+    /// # use std::time::{Duration, Instant};
+    /// fn get_names(conn: &Connection) -> Result<Vec<String>> {
+    ///     let mut stmt = conn.prepare("SELECT name FROM people")?;
+    ///     let deadline = Instant::now() + Duration::from_secs(2);
+    ///     stmt.query_with_deadline(NO_PARAMS, deadline, |mut rows| {
+    ///         let mut names = Vec::new();
+    ///         while let Some(row) = rows.next()? {
+    ///             names.push(row.get(0)?);
+    ///         }
+    ///         Ok(names)
+    ///     })
+    /// }
+    /// ```
+    ///
+    /// ## Failure
+    ///
+    /// Will return `Err` if binding parameters fails, if the deadline is
+    /// reached, or if `f` fails.
+    #[cfg(feature = "hooks")]
+    pub fn query_with_deadline<T, P, F>(&mut self, params: P, deadline: Instant, f: F) -> Result<T>
+    where
+        P: IntoIterator,
+        P::Item: ToSql,
+        F: FnOnce(Rows<'_>) -> Result<T>,
+    {
+        // Number of virtual machine instructions between two deadline checks.
+        const DEADLINE_CHECK_OPS: c_int = 1000;
+
+        // Restores the previous progress handler, even if `f` panics.
+        struct Restore<'conn> {
+            conn: &'conn Connection,
+            previous: Option<crate::hooks::ProgressHandler>,
+        }
+
+        impl Drop for Restore<'_> {
+            fn drop(&mut self) {
+                let previous = self.previous.take();
+                self.conn.db.borrow_mut().set_progress_handler(previous);
+            }
+        }
+
+        let conn = self.conn;
+        let previous = conn
+            .db
+            .borrow_mut()
+            .progress_handler(DEADLINE_CHECK_OPS, Some(move || Instant::now() >= deadline));
+        let _restore = Restore { conn, previous };
+        self.query(params).and_then(f)
+    }
+
     /// Execute the prepared statement with named parameter(s), returning a
     /// handle for the resulting rows. If any parameters that were in the
     /// prepared statement are not included in `params`, they will continue
@@ -200,6 +293,59 @@ impl Statement<'_> {
         Ok(Rows::new(self))
     }
 
+    /// Execute the prepared statement with parameters bound by `params`,
+    /// returning a handle for the resulting rows.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if binding parameters fails.
+    pub fn query_params<T: ToParams + ?Sized>(&mut self, params: &T) -> Result<Rows<'_>> {
+        self.check_readonly()?;
+        params.bind_params(self)?;
+        Ok(Rows::new(self))
+    }
+
+    /// Executes the prepared statement and converts the resulting rows to `T`
+    /// with `FromRow`, returning an iterator over the results.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,no_run
+    /// # use rusqlite::{Connection, FromRow, Result, Row, NO_PARAMS};
+    /// struct Person {
+    ///     name: String,
+    ///     age: u32,
+    /// }
+    ///
+    /// impl FromRow for Person {
+    ///     fn from_row(row: &Row<'_>) -> Result<Person> {
+    ///         Ok(Person {
+    ///             name: row.get("name")?,
+    ///             age: row.get("age")?,
+    ///         })
+    ///     }
+    /// }
+    ///
+    /// fn get_people(conn: &Connection) -> Result<Vec<Person>> {
+    ///     let mut stmt = conn.prepare("SELECT name, age FROM person")?;
+    ///     let people = stmt.query_as::<Person, _>(NO_PARAMS)?;
+    ///     people.collect()
+    /// }
+    /// ```
+    ///
+    /// ## Failure
+    ///
+    /// Will return `Err` if binding parameters fails.
+    #[allow(clippy::type_complexity)]
+    pub fn query_as<T, P>(&mut self, params: P) -> Result<MappedRows<'_, fn(&Row<'_>) -> Result<T>>>
+    where
+        T: FromRow,
+        P: IntoIterator,
+        P::Item: ToSql,
+    {
+        self.query_map(params, T::from_row as fn(&Row<'_>) -> Result<T>)
+    }
+
     /// Executes the prepared statement and maps a function over the resulting
     /// rows, returning an iterator over the mapped function results.
     ///
@@ -423,6 +569,24 @@ impl Statement<'_> {
         Ok(self.stmt.bind_parameter_index(&c_name))
     }
 
+    /// Bind `param` to the parameter at `one_based_col_index` (see
+    /// `parameter_index`).
+    ///
+    /// Parameters bound this way are used by the next execution of the
+    /// statement through `execute_params` or `query_params`.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the index is out of range or if the conversion
+    /// of `param` fails.
+    pub fn raw_bind_parameter<T: ToSql>(
+        &mut self,
+        one_based_col_index: usize,
+        param: &T,
+    ) -> Result<()> {
+        self.bind_parameter(param, one_based_col_index)
+    }
+
     fn bind_parameters<P>(&mut self, params: P) -> Result<()>
     where
         P: IntoIterator,
@@ -0,0 +1,242 @@
+//! Run-time status counters
+//!
+//! See [Database Connection Status](https://sqlite.org/c3ref/db_status.html)
+//! and [SQLite Runtime Status](https://sqlite.org/c3ref/status.html).
+
+use std::os::raw::c_int;
+
+use crate::ffi;
+use crate::{Connection, Result};
+
+/// Database connection status counters. Depending on the SQLite version,
+/// some counters may not be available.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DbStatus {
+    /// Equivalent to SQLITE_DBSTATUS_LOOKASIDE_USED
+    LookasideUsed = 0,
+    /// Equivalent to SQLITE_DBSTATUS_CACHE_USED
+    CacheUsed = 1,
+    /// Equivalent to SQLITE_DBSTATUS_SCHEMA_USED
+    SchemaUsed = 2,
+    /// Equivalent to SQLITE_DBSTATUS_STMT_USED
+    StmtUsed = 3,
+    /// Equivalent to SQLITE_DBSTATUS_LOOKASIDE_HIT (3.7.0)
+    LookasideHit = 4,
+    /// Equivalent to SQLITE_DBSTATUS_LOOKASIDE_MISS_SIZE (3.7.0)
+    LookasideMissSize = 5,
+    /// Equivalent to SQLITE_DBSTATUS_LOOKASIDE_MISS_FULL (3.7.0)
+    LookasideMissFull = 6,
+    /// Equivalent to SQLITE_DBSTATUS_CACHE_HIT (3.7.9)
+    CacheHit = 7,
+    /// Equivalent to SQLITE_DBSTATUS_CACHE_MISS (3.7.9)
+    CacheMiss = 8,
+    /// Equivalent to SQLITE_DBSTATUS_CACHE_WRITE (3.7.12)
+    CacheWrite = 9,
+    /// Equivalent to SQLITE_DBSTATUS_DEFERRED_FKS (3.8.0)
+    DeferredFks = 10,
+    /// Equivalent to SQLITE_DBSTATUS_CACHE_USED_SHARED (3.10.0)
+    CacheUsedShared = 11,
+    /// Equivalent to SQLITE_DBSTATUS_CACHE_SPILL (3.23.0)
+    CacheSpill = 12,
+}
+
+/// Global status counters, shared by all connections.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    /// Equivalent to SQLITE_STATUS_MEMORY_USED
+    MemoryUsed = 0,
+    /// Equivalent to SQLITE_STATUS_PAGECACHE_USED
+    PagecacheUsed = 1,
+    /// Equivalent to SQLITE_STATUS_PAGECACHE_OVERFLOW
+    PagecacheOverflow = 2,
+    /// Equivalent to SQLITE_STATUS_MALLOC_SIZE
+    MallocSize = 5,
+    /// Equivalent to SQLITE_STATUS_PARSER_STACK
+    ParserStack = 6,
+    /// Equivalent to SQLITE_STATUS_PAGECACHE_SIZE
+    PagecacheSize = 7,
+    /// Equivalent to SQLITE_STATUS_MALLOC_COUNT (3.7.0)
+    MallocCount = 9,
+}
+
+/// Returns the current and highwater values of a global `status` counter.
+/// If `reset` is `true`, the highwater mark is reset to the current value.
+///
+/// Memory counters are only tracked when SQLite is built with
+/// `SQLITE_DEFAULT_MEMSTATUS` enabled, which is the default.
+#[cfg(feature = "modern_sqlite")]
+pub fn status(status: Status, reset: bool) -> Result<(i64, i64)> {
+    let mut current = 0;
+    let mut highwater = 0;
+    check!(unsafe {
+        ffi::sqlite3_status64(
+            status as c_int,
+            &mut current,
+            &mut highwater,
+            reset as c_int,
+        )
+    });
+    Ok((current, highwater))
+}
+
+/// Returns the current and highwater values of a global `status` counter.
+/// If `reset` is `true`, the highwater mark is reset to the current value.
+///
+/// Memory counters are only tracked when SQLite is built with
+/// `SQLITE_DEFAULT_MEMSTATUS` enabled, which is the default.
+#[cfg(not(feature = "modern_sqlite"))]
+pub fn status(status: Status, reset: bool) -> Result<(i64, i64)> {
+    let mut current = 0;
+    let mut highwater = 0;
+    check!(unsafe {
+        ffi::sqlite3_status(
+            status as c_int,
+            &mut current,
+            &mut highwater,
+            reset as c_int,
+        )
+    });
+    Ok((current.into(), highwater.into()))
+}
+
+/// A snapshot of all `DbStatus` counters of a connection.
+///
+/// Counters not supported by the SQLite library in use are reported as 0.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// Number of lookaside memory slots currently checked out.
+    pub lookaside_used: i32,
+    /// Highest number of lookaside memory slots checked out at once.
+    pub lookaside_used_highwater: i32,
+    /// Number of allocations satisfied from lookaside memory.
+    pub lookaside_hit: i32,
+    /// Number of allocations which were too large for lookaside memory.
+    pub lookaside_miss_size: i32,
+    /// Number of allocations made while all lookaside memory was in use.
+    pub lookaside_miss_full: i32,
+    /// Bytes of heap memory used by the page caches.
+    pub cache_used: i32,
+    /// Like `cache_used`, with shared caches divided among their users.
+    pub cache_used_shared: i32,
+    /// Number of page cache hits.
+    pub cache_hit: i32,
+    /// Number of page cache misses.
+    pub cache_miss: i32,
+    /// Number of dirty cache entries written to disk.
+    pub cache_write: i32,
+    /// Number of dirty cache entries written to disk in the middle of a
+    /// transaction because the cache was full.
+    pub cache_spill: i32,
+    /// Bytes of heap memory used to store the schemas.
+    pub schema_used: i32,
+    /// Bytes of heap memory used by prepared statements.
+    pub stmt_used: i32,
+    /// Whether there are outstanding deferred foreign key violations.
+    pub deferred_fks: bool,
+}
+
+impl Connection {
+    /// Returns the current and highwater values of a connection `status`
+    /// counter. If `reset` is `true`, the highwater mark, or the current
+    /// value for the cache hit/miss/write/spill counters, is reset.
+    ///
+    /// For the lookaside hit/miss counters, only the highwater value is
+    /// meaningful.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `status` is not supported by the SQLite library
+    /// in use.
+    pub fn db_status(&self, status: DbStatus, reset: bool) -> Result<(i32, i32)> {
+        let c = self.db.borrow();
+        let mut current = 0;
+        let mut highwater = 0;
+        check!(unsafe {
+            ffi::sqlite3_db_status(
+                c.db(),
+                status as c_int,
+                &mut current,
+                &mut highwater,
+                reset as c_int,
+            )
+        });
+        Ok((current, highwater))
+    }
+
+    /// Gathers all `DbStatus` counters, without resetting them.
+    pub fn metrics(&self) -> Metrics {
+        let get = |status| self.db_status(status, false).unwrap_or((0, 0));
+        let (lookaside_used, lookaside_used_highwater) = get(DbStatus::LookasideUsed);
+        Metrics {
+            lookaside_used,
+            lookaside_used_highwater,
+            lookaside_hit: get(DbStatus::LookasideHit).1,
+            lookaside_miss_size: get(DbStatus::LookasideMissSize).1,
+            lookaside_miss_full: get(DbStatus::LookasideMissFull).1,
+            cache_used: get(DbStatus::CacheUsed).0,
+            cache_used_shared: get(DbStatus::CacheUsedShared).0,
+            cache_hit: get(DbStatus::CacheHit).0,
+            cache_miss: get(DbStatus::CacheMiss).0,
+            cache_write: get(DbStatus::CacheWrite).0,
+            cache_spill: get(DbStatus::CacheSpill).0,
+            schema_used: get(DbStatus::SchemaUsed).0,
+            stmt_used: get(DbStatus::StmtUsed).0,
+            deferred_fks: get(DbStatus::DeferredFks).0 != 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{status, DbStatus, Status};
+    use crate::{Connection, NO_PARAMS};
+
+    #[test]
+    fn test_db_status() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch("CREATE TABLE foo(x INTEGER); INSERT INTO foo VALUES (1);")
+            .unwrap();
+        let (cache_used, _) = db.db_status(DbStatus::CacheUsed, false).unwrap();
+        assert!(cache_used > 0);
+        let (schema_used, _) = db.db_status(DbStatus::SchemaUsed, false).unwrap();
+        assert!(schema_used > 0);
+
+        db.db_status(DbStatus::CacheHit, true).unwrap();
+        let _: i64 = db
+            .query_row("SELECT x FROM foo", NO_PARAMS, |r| r.get(0))
+            .unwrap();
+        let (hits, _) = db.db_status(DbStatus::CacheHit, true).unwrap();
+        assert!(hits > 0);
+        assert_eq!(0, db.db_status(DbStatus::CacheHit, false).unwrap().0);
+    }
+
+    #[test]
+    fn test_metrics() {
+        let db = Connection::open_in_memory().unwrap();
+        db.execute_batch(
+            "PRAGMA foreign_keys = ON;
+             CREATE TABLE parent(id INTEGER PRIMARY KEY);
+             CREATE TABLE child(p REFERENCES parent(id) DEFERRABLE INITIALLY DEFERRED);
+             BEGIN;
+             INSERT INTO child VALUES (1);",
+        )
+        .unwrap();
+        let metrics = db.metrics();
+        assert!(metrics.cache_used > 0);
+        assert!(metrics.schema_used > 0);
+        assert!(metrics.deferred_fks);
+        assert!(metrics.lookaside_used_highwater >= metrics.lookaside_used);
+        db.execute_batch("ROLLBACK").unwrap();
+        assert!(!db.metrics().deferred_fks);
+    }
+
+    #[test]
+    fn test_status() {
+        let (current, highwater) = status(Status::MemoryUsed, false).unwrap();
+        assert!(highwater >= current);
+        let (count, _) = status(Status::MallocCount, false).unwrap();
+        assert!(count >= 0);
+    }
+}
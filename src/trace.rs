@@ -1,7 +1,15 @@
 //! Tracing and profiling functions. Error and warning log.
 
+#[cfg(feature = "modern_sqlite")]
+use std::borrow::Cow;
 use std::ffi::{CStr, CString};
+#[cfg(feature = "modern_sqlite")]
+use std::fmt;
+#[cfg(feature = "modern_sqlite")]
+use std::marker::PhantomData;
 use std::mem;
+#[cfg(feature = "modern_sqlite")]
+use std::os::raw::c_uint;
 use std::os::raw::{c_char, c_int, c_void};
 use std::panic::catch_unwind;
 use std::ptr;
@@ -9,6 +17,8 @@ use std::time::Duration;
 
 use super::ffi;
 use crate::error::error_from_sqlite_code;
+#[cfg(feature = "modern_sqlite")]
+use crate::StatementStatus;
 use crate::{Connection, Result};
 
 /// Set up the process-wide SQLite error logging callback.
@@ -123,13 +133,176 @@ impl Connection {
     }
 }
 
+#[cfg(feature = "modern_sqlite")] // 3.14.0
+bitflags::bitflags! {
+    #[doc = "Events passed to the tracer registered with `Connection::trace_v2`."]
+    #[doc = "See [sqlite3_trace_v2](https://sqlite.org/c3ref/c_trace.html) for details."]
+    #[repr(C)]
+    pub struct TraceEventMask: ::std::os::raw::c_uint {
+        const SQLITE_TRACE_STMT    = 0x01;
+        const SQLITE_TRACE_PROFILE = 0x02;
+        const SQLITE_TRACE_ROW     = 0x04;
+        const SQLITE_TRACE_CLOSE   = 0x08;
+    }
+}
+
+/// An event passed to the tracer registered with `Connection::trace_v2`.
+#[cfg(feature = "modern_sqlite")] // 3.14.0
+#[derive(Debug)]
+pub enum TraceEvent<'s> {
+    /// A statement starts running. `sql` is the SQL text with its bound
+    /// parameters expanded, or a comment naming the trigger for the
+    /// statements of a trigger.
+    Stmt {
+        stmt: TraceStatement<'s>,
+        sql: &'s str,
+    },
+    /// A statement finished running in `duration`.
+    Profile {
+        stmt: TraceStatement<'s>,
+        duration: Duration,
+    },
+    /// A statement generated a row.
+    Row { stmt: TraceStatement<'s> },
+    /// The connection is closing.
+    Close,
+}
+
+/// A statement being traced.
+#[cfg(feature = "modern_sqlite")] // 3.14.0
+pub struct TraceStatement<'s> {
+    stmt: *mut ffi::sqlite3_stmt,
+    phantom: PhantomData<&'s ()>,
+}
+
+#[cfg(feature = "modern_sqlite")] // 3.14.0
+impl TraceStatement<'_> {
+    /// Returns the SQL text of the statement, as given to prepare.
+    pub fn sql(&self) -> Cow<'_, str> {
+        unsafe { String::from_utf8_lossy(CStr::from_ptr(ffi::sqlite3_sql(self.stmt)).to_bytes()) }
+    }
+
+    /// Returns the SQL text of the statement with its bound parameters
+    /// expanded.
+    pub fn expanded_sql(&self) -> Option<String> {
+        unsafe {
+            let p = ffi::sqlite3_expanded_sql(self.stmt);
+            if p.is_null() {
+                return None;
+            }
+            let sql = String::from_utf8_lossy(CStr::from_ptr(p).to_bytes()).into_owned();
+            ffi::sqlite3_free(p as *mut c_void);
+            Some(sql)
+        }
+    }
+
+    /// Get the value for one of the status counters of the statement.
+    pub fn get_status(&self, status: StatementStatus) -> i32 {
+        unsafe { ffi::sqlite3_stmt_status(self.stmt, status as c_int, 0) }
+    }
+}
+
+#[cfg(feature = "modern_sqlite")] // 3.14.0
+impl fmt::Debug for TraceStatement<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TraceStatement")
+            .field("sql", &self.sql())
+            .finish()
+    }
+}
+
+#[cfg(feature = "modern_sqlite")] // 3.14.0
+pub(crate) type BoxedTracer = Box<dyn for<'s> FnMut(TraceEvent<'s>) + Send + 'static>;
+
+#[cfg(feature = "modern_sqlite")] // 3.14.0
+impl Connection {
+    /// Register or clear a callback function to be invoked on the events
+    /// selected by `mask`.
+    ///
+    /// If `mask` is empty or `tracer` is `None`, tracing is disabled. There
+    /// can only be a single tracer defined for each database connection,
+    /// shared with `trace` and `profile`: setting a new tracer clears the old
+    /// one.
+    pub fn trace_v2<F>(&self, mask: TraceEventMask, tracer: Option<F>)
+    where
+        F: for<'s> FnMut(TraceEvent<'s>) + Send + 'static,
+    {
+        unsafe extern "C" fn call_boxed_closure(
+            event: c_uint,
+            p_ctx: *mut c_void,
+            p: *mut c_void,
+            x: *mut c_void,
+        ) -> c_int {
+            let stmt = || TraceStatement {
+                stmt: p as *mut ffi::sqlite3_stmt,
+                phantom: PhantomData,
+            };
+            let _ = catch_unwind(|| {
+                let boxed_tracer = p_ctx as *mut BoxedTracer;
+                match TraceEventMask::from_bits_truncate(event) {
+                    TraceEventMask::SQLITE_TRACE_STMT => {
+                        let stmt = stmt();
+                        let x =
+                            String::from_utf8_lossy(CStr::from_ptr(x as *const c_char).to_bytes());
+                        let sql = if x.starts_with("--") {
+                            x.into_owned()
+                        } else {
+                            stmt.expanded_sql().unwrap_or_else(|| x.into_owned())
+                        };
+                        (*boxed_tracer)(TraceEvent::Stmt { stmt, sql: &sql })
+                    }
+                    TraceEventMask::SQLITE_TRACE_PROFILE => {
+                        let nanoseconds = *(x as *const i64);
+                        (*boxed_tracer)(TraceEvent::Profile {
+                            stmt: stmt(),
+                            duration: Duration::from_nanos(nanoseconds as u64),
+                        })
+                    }
+                    TraceEventMask::SQLITE_TRACE_ROW => {
+                        (*boxed_tracer)(TraceEvent::Row { stmt: stmt() })
+                    }
+                    TraceEventMask::SQLITE_TRACE_CLOSE => (*boxed_tracer)(TraceEvent::Close),
+                    _ => {}
+                }
+            });
+            0
+        }
+
+        let mut c = self.db.borrow_mut();
+        match tracer {
+            Some(tracer) if !mask.is_empty() => {
+                let mut boxed_tracer: Box<BoxedTracer> = Box::new(Box::new(tracer));
+                unsafe {
+                    ffi::sqlite3_trace_v2(
+                        c.db(),
+                        mask.bits(),
+                        Some(call_boxed_closure),
+                        &mut *boxed_tracer as *mut BoxedTracer as *mut _,
+                    );
+                }
+                c.tracer = Some(boxed_tracer);
+            }
+            _ => {
+                unsafe { ffi::sqlite3_trace_v2(c.db(), 0, None, ptr::null_mut()) };
+                c.tracer = None;
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use lazy_static::lazy_static;
+    #[cfg(feature = "modern_sqlite")]
+    use std::sync::Arc;
     use std::sync::Mutex;
     use std::time::Duration;
 
+    #[cfg(feature = "modern_sqlite")]
+    use super::{TraceEvent, TraceEventMask};
     use crate::Connection;
+    #[cfg(feature = "modern_sqlite")]
+    use crate::{StatementStatus, NO_PARAMS};
 
     #[test]
     fn test_trace() {
@@ -179,4 +352,60 @@ mod test {
         assert_eq!(profiled.len(), 1);
         assert_eq!(profiled[0].0, "PRAGMA application_id = 1");
     }
+
+    #[test]
+    #[cfg(feature = "modern_sqlite")]
+    fn test_trace_v2() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let db = Connection::open_in_memory().unwrap();
+        let traced = events.clone();
+        db.trace_v2(
+            TraceEventMask::all(),
+            Some(move |event: TraceEvent<'_>| {
+                let event = match event {
+                    TraceEvent::Stmt { sql, .. } => format!("stmt {}", sql),
+                    TraceEvent::Profile { stmt, .. } => format!(
+                        "profile {} {}",
+                        stmt.sql(),
+                        stmt.get_status(StatementStatus::VmStep) > 0
+                    ),
+                    TraceEvent::Row { stmt } => format!("row {:?}", stmt.expanded_sql()),
+                    TraceEvent::Close => "close".to_owned(),
+                };
+                traced.lock().unwrap().push(event);
+            }),
+        );
+        db.query_row("SELECT ?", &["hello"], |_| Ok(())).unwrap();
+        db.trace_v2(
+            TraceEventMask::SQLITE_TRACE_CLOSE,
+            Some(|_: TraceEvent<'_>| {}),
+        );
+        db.trace_v2(
+            TraceEventMask::SQLITE_TRACE_CLOSE,
+            None::<fn(TraceEvent<'_>)>,
+        );
+        db.query_row("SELECT 1", NO_PARAMS, |_| Ok(())).unwrap();
+
+        let traced = events.clone();
+        db.trace_v2(
+            TraceEventMask::SQLITE_TRACE_CLOSE,
+            Some(move |event: TraceEvent<'_>| {
+                if let TraceEvent::Close = event {
+                    traced.lock().unwrap().push("close".to_owned());
+                }
+            }),
+        );
+        db.close().unwrap();
+
+        let events = events.lock().unwrap();
+        assert_eq!(
+            *events,
+            vec![
+                "stmt SELECT 'hello'",
+                "row Some(\"SELECT 'hello'\")",
+                "profile SELECT ? true",
+                "close",
+            ]
+        );
+    }
 }
@@ -0,0 +1,305 @@
+//! Database compaction.
+//!
+//! `Connection::vacuum` rebuilds a whole database in one go, holding a write
+//! lock for the whole run. With `auto_vacuum` set to `INCREMENTAL`, an
+//! `IncrementalVacuum` instead reclaims free pages a few at a time, so that
+//! other connections can write between steps.
+//!
+//! ```rust,no_run
+//! # use rusqlite::{vacuum, Connection, Result};
+//! # use std::time;
+//!
+//! fn compact(conn: &Connection, progress: fn(vacuum::Progress)) -> Result<()> {
+//!     let vacuum = vacuum::IncrementalVacuum::new(conn)?;
+//!     vacuum.run_to_completion(100, time::Duration::from_millis(250), Some(progress))
+//! }
+//! ```
+
+use std::cell::Cell;
+use std::os::raw::c_int;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use crate::ffi;
+use crate::pragma::Sql;
+use crate::{AutoVacuum, Connection, DatabaseName, Error, ErrorCode, Result};
+
+impl Connection {
+    /// Rebuild the `name` database, repacking it into a minimal amount of
+    /// disk space.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if a transaction is active or if the database is
+    /// locked by another connection.
+    pub fn vacuum(&self, name: DatabaseName<'_>) -> Result<()> {
+        let mut sql = Sql::new();
+        sql.push_keyword("VACUUM")?;
+        sql.push_space();
+        sql.push_schema_name(name);
+        self.execute_batch(&sql)
+    }
+
+    /// Write a vacuumed copy of the `main` database to `path`, leaving the
+    /// original database unchanged.
+    ///
+    /// Requires SQLite 3.27.0 or later.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `path` is not valid UTF-8, if a file already
+    /// exists at `path` or if the underlying SQLite call fails.
+    pub fn vacuum_into<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let path = path
+            .to_str()
+            .ok_or_else(|| Error::InvalidPath(path.to_owned()))?;
+        self.execute("VACUUM INTO ?", &[path]).map(|_| ())
+    }
+
+    /// Move up to `num_pages` pages from the freelist of the `name` database
+    /// back to the file system. If `num_pages` is not positive, the entire
+    /// freelist is cleared.
+    ///
+    /// This is a no-op unless `auto_vacuum` is `INCREMENTAL`.
+    pub fn incremental_vacuum(&self, name: DatabaseName<'_>, num_pages: c_int) -> Result<Progress> {
+        self.pragma(Some(name), "incremental_vacuum", &num_pages, |_| Ok(()))?;
+        Progress::query(self, name)
+    }
+}
+
+/// Possible successful results of calling `IncrementalVacuum::step`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StepResult {
+    /// The freelist is empty.
+    Done,
+
+    /// The step was successful but there are still free pages to reclaim.
+    More,
+
+    /// The step failed because the database is busy. The step can be
+    /// retried.
+    Busy,
+
+    /// The step failed because of a locked table. The step can be retried.
+    Locked,
+}
+
+/// Struct specifying the progress of an incremental vacuum. The percentage
+/// completion can be calculated as `(pagecount - remaining) / pagecount`
+/// relative to the first `Progress` observed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Progress {
+    /// Number of unused pages left on the freelist.
+    pub remaining: i64,
+    /// Total number of pages in the database.
+    pub pagecount: i64,
+}
+
+impl Progress {
+    fn query(conn: &Connection, name: DatabaseName<'_>) -> Result<Progress> {
+        Ok(Progress {
+            remaining: conn.pragma_query_value(Some(name), "freelist_count", |row| row.get(0))?,
+            pagecount: conn.pragma_query_value(Some(name), "page_count", |row| row.get(0))?,
+        })
+    }
+}
+
+/// A handle to reclaim the free pages of a database a few at a time.
+pub struct IncrementalVacuum<'conn> {
+    conn: &'conn Connection,
+    name: DatabaseName<'conn>,
+    progress: Cell<Progress>,
+}
+
+impl IncrementalVacuum<'_> {
+    /// Attempt to create a new handle for the `main` database.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `auto_vacuum` is not `INCREMENTAL`.
+    pub fn new(conn: &Connection) -> Result<IncrementalVacuum<'_>> {
+        IncrementalVacuum::new_with_name(conn, DatabaseName::Main)
+    }
+
+    /// Attempt to create a new handle for the `name` database.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if `auto_vacuum` is not `INCREMENTAL`.
+    pub fn new_with_name<'conn>(
+        conn: &'conn Connection,
+        name: DatabaseName<'conn>,
+    ) -> Result<IncrementalVacuum<'conn>> {
+        let mode: AutoVacuum =
+            conn.pragma_query_value(Some(name), "auto_vacuum", |row| row.get(0))?;
+        if mode != AutoVacuum::Incremental {
+            return Err(Error::SqliteFailure(
+                ffi::Error::new(ffi::SQLITE_MISUSE),
+                Some(format!("auto_vacuum is {:?}, not Incremental", mode)),
+            ));
+        }
+        Ok(IncrementalVacuum {
+            conn,
+            name,
+            progress: Cell::new(Progress::query(conn, name)?),
+        })
+    }
+
+    /// Gets the progress of the vacuum as of the last call to `step`.
+    pub fn progress(&self) -> Progress {
+        self.progress.get()
+    }
+
+    /// Attempts to reclaim the given number of pages. If `num_pages` is not
+    /// positive, will attempt to reclaim all free pages at once.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if the underlying pragma fails with an error code
+    /// other than `BUSY` or `LOCKED`. These are transient errors and are
+    /// therefore returned as possible `Ok` values.
+    pub fn step(&self, num_pages: c_int) -> Result<StepResult> {
+        match self.conn.incremental_vacuum(self.name, num_pages) {
+            Ok(progress) => {
+                self.progress.set(progress);
+                if progress.remaining == 0 {
+                    Ok(StepResult::Done)
+                } else {
+                    Ok(StepResult::More)
+                }
+            }
+            Err(Error::SqliteFailure(err, _)) if err.code == ErrorCode::DatabaseBusy => {
+                Ok(StepResult::Busy)
+            }
+            Err(Error::SqliteFailure(err, _)) if err.code == ErrorCode::DatabaseLocked => {
+                Ok(StepResult::Locked)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Attempts to reclaim all free pages. Will call `step(pages_per_step)`
+    /// as many times as necessary, sleeping for `pause_between_pages` between
+    /// each call to let other connections write to the database.
+    ///
+    /// If `progress` is not `None`, it will be called after each step with the
+    /// current progress of the vacuum.
+    ///
+    /// # Failure
+    ///
+    /// Will return `Err` if any of the calls to `step` return `Err`.
+    pub fn run_to_completion(
+        &self,
+        pages_per_step: c_int,
+        pause_between_pages: Duration,
+        progress: Option<fn(Progress)>,
+    ) -> Result<()> {
+        use self::StepResult::{Busy, Done, Locked, More};
+
+        assert!(pages_per_step > 0, "pages_per_step must be positive");
+
+        if self.progress().remaining == 0 {
+            return Ok(());
+        }
+        loop {
+            let r = self.step(pages_per_step)?;
+            if let Some(progress) = progress {
+                progress(self.progress())
+            }
+            match r {
+                More | Busy | Locked => thread::sleep(pause_between_pages),
+                Done => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{IncrementalVacuum, Progress, StepResult};
+    use crate::{AutoVacuum, Connection, DatabaseName, NO_PARAMS};
+    use std::time::Duration;
+
+    fn fragmented_db(path: &std::path::Path) -> Connection {
+        let db = Connection::open(path).unwrap();
+        db.set_auto_vacuum(AutoVacuum::Incremental).unwrap();
+        db.execute_batch(
+            "CREATE TABLE foo(x BLOB);
+             WITH RECURSIVE c(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM c WHERE i < 50)
+             INSERT INTO foo SELECT zeroblob(4096) FROM c;
+             DELETE FROM foo;",
+        )
+        .unwrap();
+        db
+    }
+
+    #[test]
+    fn test_incremental_vacuum() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = fragmented_db(&dir.path().join("incremental.db3"));
+
+        let vacuum = IncrementalVacuum::new(&db).unwrap();
+        let start = vacuum.progress();
+        assert!(start.remaining > 10);
+
+        assert_eq!(StepResult::More, vacuum.step(10).unwrap());
+        assert_eq!(start.remaining - 10, vacuum.progress().remaining);
+        assert_eq!(start.pagecount - 10, vacuum.progress().pagecount);
+
+        vacuum
+            .run_to_completion(10, Duration::from_millis(0), None)
+            .unwrap();
+        assert_eq!(0, vacuum.progress().remaining);
+        assert_eq!(StepResult::Done, vacuum.step(10).unwrap());
+        assert_eq!(
+            Progress {
+                remaining: 0,
+                pagecount: start.pagecount - start.remaining,
+            },
+            db.incremental_vacuum(DatabaseName::Main, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_incremental_vacuum_requires_mode() {
+        let db = Connection::open_in_memory().unwrap();
+        assert!(IncrementalVacuum::new(&db).is_err());
+    }
+
+    #[test]
+    fn test_vacuum() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = fragmented_db(&dir.path().join("vacuum.db3"));
+        db.execute("INSERT INTO foo VALUES (42)", NO_PARAMS)
+            .unwrap();
+        assert!(db.freelist_count() > 0);
+
+        let copy = dir.path().join("copy.db3");
+        db.vacuum_into(&copy).unwrap();
+        assert!(db.freelist_count() > 0);
+        let dst = Connection::open(&copy).unwrap();
+        let x: i64 = dst
+            .query_row("SELECT x FROM foo", NO_PARAMS, |r| r.get(0))
+            .unwrap();
+        assert_eq!(42, x);
+        assert_eq!(0, dst.freelist_count());
+        // the target must not exist
+        assert!(db.vacuum_into(&copy).is_err());
+
+        db.vacuum(DatabaseName::Main).unwrap();
+        assert_eq!(0, db.freelist_count());
+    }
+
+    trait FreelistCount {
+        fn freelist_count(&self) -> i64;
+    }
+
+    impl FreelistCount for Connection {
+        fn freelist_count(&self) -> i64 {
+            self.pragma_query_value(None, "freelist_count", |r| r.get(0))
+                .unwrap()
+        }
+    }
+}
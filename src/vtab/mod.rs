@@ -3,16 +3,20 @@
 //! Follow these steps to create your own virtual table:
 //! 1. Write implemenation of `VTab` and `VTabCursor` traits.
 //! 2. Create an instance of the `Module` structure specialized for `VTab` impl.
-//! from step 1.
+//! from step 1 (`update_module` if it also implements `UpdateVTab`).
 //! 3. Register your `Module` structure using `Connection.create_module`.
 //! 4. Run a `CREATE VIRTUAL TABLE` command that specifies the new module in the
 //! `USING` clause.
 //!
 //! (See [SQLite doc](http://sqlite.org/vtab.html))
 use std::borrow::Cow::{self, Borrowed, Owned};
+#[cfg(feature = "functions")]
+use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::marker::Sync;
 use std::os::raw::{c_char, c_int, c_void};
+#[cfg(feature = "functions")]
+use std::panic::UnwindSafe;
 use std::ptr;
 use std::slice;
 
@@ -20,6 +24,8 @@ use crate::context::set_result;
 use crate::error::error_from_sqlite_code;
 use crate::ffi;
 pub use crate::ffi::{sqlite3_vtab, sqlite3_vtab_cursor};
+#[cfg(feature = "functions")]
+use crate::types::ToSqlOutput;
 use crate::types::{FromSql, FromSqlError, ToSql, ValueRef};
 use crate::{str_to_cstring, Connection, Error, InnerConnection, Result};
 
@@ -83,6 +89,8 @@ fn zeroed_module() -> ffi::sqlite3_module {
 pub fn read_only_module<T: CreateVTab>(version: c_int) -> Module<T> {
     // The xConnect and xCreate methods do the same thing, but they must be
     // different so that the virtual table is not an eponymous virtual table.
+    // `..zeroed_module()` has no effect when all the fields are specified.
+    #[allow(clippy::needless_update)]
     let ffi_module = ffi::sqlite3_module {
         iVersion: version,
         xCreate: Some(rust_create::<T>),
@@ -102,11 +110,16 @@ pub fn read_only_module<T: CreateVTab>(version: c_int) -> Module<T> {
         xSync: None,
         xCommit: None,
         xRollback: None,
+        #[cfg(feature = "functions")]
+        xFindFunction: Some(rust_find_function::<T>),
+        #[cfg(not(feature = "functions"))]
         xFindFunction: None,
-        xRename: None,
+        xRename: Some(rust_rename::<T>),
         xSavepoint: None,
         xRelease: None,
         xRollbackTo: None,
+        #[cfg(feature = "vtab_v3")]
+        xShadowName: Some(rust_shadow_name::<T>),
         ..zeroed_module()
     };
     Module {
@@ -122,6 +135,8 @@ pub fn eponymous_only_module<T: VTab>(version: c_int) -> Module<T> {
     // A virtual table is eponymous if its xCreate method is the exact same function
     // as the xConnect method For eponymous-only virtual tables, the xCreate
     // method is NULL
+    // `..zeroed_module()` has no effect when all the fields are specified.
+    #[allow(clippy::needless_update)]
     let ffi_module = ffi::sqlite3_module {
         iVersion: version,
         xCreate: None,
@@ -141,11 +156,16 @@ pub fn eponymous_only_module<T: VTab>(version: c_int) -> Module<T> {
         xSync: None,
         xCommit: None,
         xRollback: None,
+        #[cfg(feature = "functions")]
+        xFindFunction: Some(rust_find_function::<T>),
+        #[cfg(not(feature = "functions"))]
         xFindFunction: None,
-        xRename: None,
+        xRename: Some(rust_rename::<T>),
         xSavepoint: None,
         xRelease: None,
         xRollbackTo: None,
+        #[cfg(feature = "vtab_v3")]
+        xShadowName: Some(rust_shadow_name::<T>),
         ..zeroed_module()
     };
     Module {
@@ -154,10 +174,67 @@ pub fn eponymous_only_module<T: VTab>(version: c_int) -> Module<T> {
     }
 }
 
+/// Create a modifiable virtual table implementation.
+///
+/// Step 2 of [Creating New Virtual Table Implementations](https://sqlite.org/vtab.html#creating_new_virtual_table_implementations).
+pub fn update_module<T: UpdateVTab>(version: c_int) -> Module<T> {
+    let mut module = read_only_module::<T>(version);
+    module.base.xUpdate = Some(rust_update::<T>);
+    module
+}
+
+impl<T: TransactionVTab> Module<T> {
+    /// Register the transaction methods of `T`.
+    ///
+    /// SQLite only calls the savepoint methods if the module `version` is
+    /// at least 2.
+    pub fn with_transactions(mut self) -> Module<T> {
+        self.base.xBegin = Some(rust_begin::<T>);
+        self.base.xSync = Some(rust_sync::<T>);
+        self.base.xCommit = Some(rust_commit::<T>);
+        self.base.xRollback = Some(rust_rollback::<T>);
+        self.base.xSavepoint = Some(rust_savepoint::<T>);
+        self.base.xRelease = Some(rust_release::<T>);
+        self.base.xRollbackTo = Some(rust_rollback_to::<T>);
+        self
+    }
+}
+
 pub struct VTabConnection(*mut ffi::sqlite3);
 
+/// Virtual table configuration options
+#[cfg(feature = "vtab_v3")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(i32)]
+pub enum VTabConfig {
+    /// The `xUpdate` method honors `ON CONFLICT` and may be interrupted by a
+    /// constraint violation.
+    ConstraintSupport = 1,
+    /// The virtual table is safe to use from triggers and views even when
+    /// the schema is not trusted. (3.31.0)
+    Innocuous = 2,
+    /// The virtual table cannot be used from triggers or views. (3.31.0)
+    DirectOnly = 3,
+}
+
 impl VTabConnection {
-    // TODO sqlite3_vtab_config (http://sqlite.org/c3ref/vtab_config.html)
+    /// Configure the virtual table being created or connected.
+    ///
+    /// Must be called from `VTab::connect` or `CreateVTab::create`.
+    /// (See [SQLite doc](https://sqlite.org/c3ref/vtab_config.html))
+    #[cfg(feature = "vtab_v3")]
+    pub fn config(&mut self, config: VTabConfig) -> Result<()> {
+        let rc = match config {
+            VTabConfig::ConstraintSupport => unsafe {
+                ffi::sqlite3_vtab_config(self.0, config as c_int, 1 as c_int)
+            },
+            VTabConfig::Innocuous | VTabConfig::DirectOnly => unsafe {
+                ffi::sqlite3_vtab_config(self.0, config as c_int)
+            },
+        };
+        check!(rc);
+        Ok(())
+    }
 
     // TODO sqlite3_vtab_on_conflict (http://sqlite.org/c3ref/vtab_on_conflict.html)
 
@@ -212,8 +289,63 @@ pub trait VTab: Sized {
     /// Create a new cursor used for accessing a virtual table.
     /// (See [SQLite doc](https://sqlite.org/vtab.html#the_xopen_method))
     fn open(&self) -> Result<Self::Cursor>;
+
+    /// Overload the SQL function `name` with `n_arg` arguments when its first
+    /// argument is a column of this virtual table. For infix operators like
+    /// `MATCH` or `LIKE`, `X MATCH Y` is evaluated as `match(Y, X)` and it is
+    /// `X` which must be a column.
+    ///
+    /// Return `None` (the default) to keep the ordinary implementation. The
+    /// returned function is kept until the table is disconnected and is reused
+    /// by later statements calling the same function.
+    /// (See [SQLite doc](https://sqlite.org/vtab.html#the_xfindfunction_method))
+    #[cfg(feature = "functions")]
+    fn find_function(&self, _n_arg: c_int, _name: &str) -> Option<VTabFunction> {
+        None
+    }
+
+    /// Return `Some(op)`, with `op` >= 150, to have the calls of a function
+    /// overloaded by `find_function` passed to `best_index` as
+    /// `SQLITE_INDEX_CONSTRAINT_FUNCTION(op)` constraints.
+    ///
+    /// Return `None` by default.
+    #[cfg(all(feature = "functions", feature = "modern_sqlite"))] // SQLite >= 3.25.0
+    fn function_constraint(&self, _n_arg: c_int, _name: &str) -> Option<u8> {
+        None
+    }
+
+    /// Return `true` if `name` is the suffix of a shadow table name of this
+    /// module: `foo_data` is a shadow table of virtual table `foo` if this
+    /// method returns `true` for `data`. Shadow tables are read-only for
+    /// ordinary SQL when `SQLITE_DBCONFIG_DEFENSIVE` is enabled.
+    ///
+    /// Only called if the module version is at least 3. Return `false` by
+    /// default.
+    /// (See [SQLite doc](https://sqlite.org/vtab.html#the_xshadowname_method))
+    #[cfg(feature = "vtab_v3")]
+    fn shadow_name(_name: &str) -> bool {
+        false
+    }
+
+    /// Rename the virtual table to `new_name` in response to an
+    /// `ALTER TABLE ... RENAME` statement. Return `Err` to reject the rename.
+    ///
+    /// Do nothing by default.
+    /// (See [SQLite doc](https://sqlite.org/vtab.html#the_xrename_method))
+    fn rename(&mut self, _new_name: &str) -> Result<()> {
+        Ok(())
+    }
 }
 
+/// Function returned by `VTab::find_function`.
+#[cfg(feature = "functions")]
+pub type VTabFunction = Box<
+    dyn FnMut(&crate::functions::Context<'_>) -> Result<ToSqlOutput<'static>>
+        + Send
+        + UnwindSafe
+        + 'static,
+>;
+
 /// Non-eponymous virtual table instance trait.
 ///
 /// (See [SQLite doc](https://sqlite.org/c3ref/vtab.html))
@@ -243,6 +375,67 @@ pub trait CreateVTab: VTab {
     }
 }
 
+/// Modifiable virtual table instance trait.
+///
+/// (See [SQLite doc](https://sqlite.org/vtab.html#the_xupdate_method))
+pub trait UpdateVTab: CreateVTab {
+    /// Delete the row with rowid `arg`.
+    fn delete(&mut self, arg: ValueRef<'_>) -> Result<()>;
+    /// Insert a new row.
+    ///
+    /// `args[0]` is NULL, `args[1]` is the rowid of the new row (or NULL if
+    /// the implementation must choose one), and `args[2]`... are the column
+    /// values. Return the rowid of the inserted row.
+    fn insert(&mut self, args: &Values<'_>) -> Result<i64>;
+    /// Update an existing row.
+    ///
+    /// `args[0]` is the rowid of the row to update, `args[1]` is its new
+    /// rowid (the same as `args[0]` unless the rowid is changed by the UPDATE
+    /// statement), and `args[2]`... are the new column values.
+    fn update(&mut self, args: &Values<'_>) -> Result<()>;
+}
+
+/// Virtual table transaction trait.
+///
+/// Use `Module::with_transactions` to register these methods.
+/// All of them do nothing by default.
+/// (See [SQLite doc](https://sqlite.org/vtab.html#the_xbegin_method))
+pub trait TransactionVTab: VTab {
+    /// Begin a transaction.
+    /// (See [SQLite doc](https://sqlite.org/vtab.html#the_xbegin_method))
+    fn begin(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Start a two-phase commit.
+    /// (See [SQLite doc](https://sqlite.org/vtab.html#the_xsync_method))
+    fn sync(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Commit the current transaction.
+    /// (See [SQLite doc](https://sqlite.org/vtab.html#the_xcommit_method))
+    fn commit(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Roll back the current transaction.
+    /// (See [SQLite doc](https://sqlite.org/vtab.html#the_xrollback_method))
+    fn rollback(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Save the current state as savepoint number `n`.
+    /// (See [SQLite doc](https://sqlite.org/vtab.html#the_xsavepoint_xrelease_and_xrollbackto_methods))
+    fn savepoint(&mut self, _n: c_int) -> Result<()> {
+        Ok(())
+    }
+    /// Invalidate all savepoints numbered `n` or higher.
+    fn release(&mut self, _n: c_int) -> Result<()> {
+        Ok(())
+    }
+    /// Revert to the state of savepoint number `n`.
+    fn rollback_to(&mut self, _n: c_int) -> Result<()> {
+        Ok(())
+    }
+}
+
 ///Index constraint operator.
 #[derive(Debug, PartialEq)]
 #[allow(non_snake_case, non_camel_case_types)]
@@ -261,6 +454,8 @@ pub enum IndexConstraintOp {
     SQLITE_INDEX_CONSTRAINT_ISNOTNULL,    // 3.21.0
     SQLITE_INDEX_CONSTRAINT_ISNULL,       // 3.21.0
     SQLITE_INDEX_CONSTRAINT_IS,           // 3.21.0
+    SQLITE_INDEX_CONSTRAINT_LIMIT,        // 3.38.0
+    SQLITE_INDEX_CONSTRAINT_OFFSET,       // 3.38.0
     SQLITE_INDEX_CONSTRAINT_FUNCTION(u8), // 3.25.0
 }
 
@@ -281,6 +476,8 @@ impl From<u8> for IndexConstraintOp {
             70 => IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_ISNOTNULL,
             71 => IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_ISNULL,
             72 => IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_IS,
+            73 => IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_LIMIT,
+            74 => IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_OFFSET,
             v => IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_FUNCTION(v),
         }
     }
@@ -352,10 +549,121 @@ impl IndexInfo {
         }
     }
 
-    // TODO idxFlags
-    // TODO colUsed
+    /// String used to identify the index
+    pub fn set_idx_str(&mut self, idx_str: &str) -> Result<()> {
+        let c_str = str_to_cstring(idx_str)?;
+        unsafe {
+            if (*self.0).needToFreeIdxStr != 0 {
+                ffi::sqlite3_free((*self.0).idxStr as *mut c_void);
+            }
+            (*self.0).idxStr = alloc(c_str.as_bytes());
+            (*self.0).needToFreeIdxStr = 1;
+        }
+        Ok(())
+    }
+
+    /// Mask of SQLITE_INDEX_SCAN_* flags.
+    #[cfg(feature = "modern_sqlite")] // SQLite >= 3.9.0
+    pub fn set_idx_flags(&mut self, flags: IndexFlags) {
+        unsafe {
+            (*self.0).idxFlags = flags.bits();
+        }
+    }
+
+    /// Mask of columns used by statement: bit `i` is set if column `i` (or
+    /// any column beyond the 63rd, for bit 63) is used.
+    #[cfg(feature = "modern_sqlite")] // SQLite >= 3.10.0
+    pub fn col_used(&self) -> u64 {
+        unsafe { (*self.0).colUsed }
+    }
+
+    /// Name of the collating sequence to be used when evaluating the
+    /// `constraint_idx`-th constraint.
+    #[cfg(feature = "modern_sqlite")] // SQLite >= 3.22.0
+    pub fn collation(&self, constraint_idx: usize) -> Result<&str> {
+        use std::ffi::CStr;
+        let idx = constraint_idx as c_int;
+        let collation = unsafe { ffi::sqlite3_vtab_collation(self.0, idx) };
+        if collation.is_null() {
+            return Err(Error::SqliteFailure(
+                ffi::Error::new(ffi::SQLITE_MISUSE),
+                Some(format!("{} is out of range", constraint_idx)),
+            ));
+        }
+        Ok(unsafe { CStr::from_ptr(collation) }.to_str()?)
+    }
+
+    /// How the result of the query will be used by `DISTINCT` or
+    /// `GROUP BY`.
+    #[cfg(feature = "vtab_planner")] // SQLite >= 3.38.0
+    pub fn distinct(&self) -> DistinctMode {
+        match unsafe { ffi::sqlite3_vtab_distinct(self.0) } {
+            1 => DistinctMode::Grouped,
+            2 => DistinctMode::Distinct,
+            3 => DistinctMode::DistinctOrdered,
+            _ => DistinctMode::Ordered,
+        }
+    }
+
+    /// Check if the `constraint_idx`-th constraint is an `IN` operator
+    /// which can be processed all at once.
+    #[cfg(feature = "vtab_planner")] // SQLite >= 3.38.0
+    pub fn is_in_constraint(&self, constraint_idx: usize) -> bool {
+        unsafe { ffi::sqlite3_vtab_in(self.0, constraint_idx as c_int, -1) != 0 }
+    }
+
+    /// Request (or not, if `handle_all` is `false`) that the whole `IN` list
+    /// of the `constraint_idx`-th constraint be passed as a single argument to
+    /// `VTabCursor::filter`, where it can be read with `Values::in_values`.
+    ///
+    /// The `constraint_idx`-th constraint must also be given an
+    /// `argv_index`. Return `false` if this constraint is not an `IN`
+    /// operator which can be processed all at once.
+    #[cfg(feature = "vtab_planner")] // SQLite >= 3.38.0
+    pub fn set_in_constraint(&mut self, constraint_idx: usize, handle_all: bool) -> bool {
+        let handle = if handle_all { 1 } else { 0 };
+        unsafe { ffi::sqlite3_vtab_in(self.0, constraint_idx as c_int, handle) != 0 }
+    }
+
+    /// Right-hand operand of the `constraint_idx`-th constraint, if it is
+    /// known when the query is planned.
+    #[cfg(feature = "vtab_planner")] // SQLite >= 3.38.0
+    pub fn rhs_value(&self, constraint_idx: usize) -> Result<Option<ValueRef<'_>>> {
+        let mut val = ptr::null_mut();
+        let rc = unsafe { ffi::sqlite3_vtab_rhs_value(self.0, constraint_idx as c_int, &mut val) };
+        match rc {
+            ffi::SQLITE_OK => Ok(Some(unsafe { ValueRef::from_value(val) })),
+            ffi::SQLITE_NOTFOUND => Ok(None),
+            _ => Err(error_from_sqlite_code(rc, None)),
+        }
+    }
+}
+
+#[cfg(feature = "modern_sqlite")] // SQLite >= 3.9.0
+bitflags::bitflags! {
+    #[doc = "Virtual table scan flags"]
+    #[doc = "See [sqlite3_index_info](https://sqlite.org/c3ref/c_index_scan_unique.html) for details."]
+    #[repr(C)]
+    pub struct IndexFlags: ::std::os::raw::c_int {
+        const NONE = 0;
+        const SQLITE_INDEX_SCAN_UNIQUE = ffi::SQLITE_INDEX_SCAN_UNIQUE;
+    }
+}
 
-    // TODO sqlite3_vtab_collation (http://sqlite.org/c3ref/vtab_collation.html)
+/// `DISTINCT` and `GROUP BY` handling expected from a virtual table scan.
+///
+/// (See [sqlite3_vtab_distinct](https://sqlite.org/c3ref/vtab_distinct.html))
+#[cfg(feature = "vtab_planner")] // SQLite >= 3.38.0
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DistinctMode {
+    /// All rows must be returned, in the order given by `order_bys`.
+    Ordered,
+    /// Rows with equal `order_bys` columns must be adjacent.
+    Grouped,
+    /// Rows with equal `order_bys` columns may be omitted, except one.
+    Distinct,
+    /// Like `Distinct`, and the remaining rows must also be ordered.
+    DistinctOrdered,
 }
 
 pub struct IndexConstraintIter<'a> {
@@ -546,6 +854,48 @@ impl Values<'_> {
             iter: self.args.iter(),
         }
     }
+
+    /// Iterate over the values of the `IN` list passed as the `idx`-th
+    /// argument (see `IndexInfo::set_in_constraint`).
+    #[cfg(feature = "vtab_planner")] // SQLite >= 3.38.0
+    pub fn in_values(&self, idx: usize) -> InValues<'_> {
+        InValues {
+            list: self.args[idx],
+            first: true,
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// Values of an `IN` list processed all at once.
+#[cfg(feature = "vtab_planner")] // SQLite >= 3.38.0
+pub struct InValues<'a> {
+    list: *mut ffi::sqlite3_value,
+    first: bool,
+    phantom: PhantomData<&'a ()>,
+}
+
+#[cfg(feature = "vtab_planner")] // SQLite >= 3.38.0
+impl<'a> fallible_iterator::FallibleIterator for InValues<'a> {
+    type Error = Error;
+    type Item = ValueRef<'a>;
+
+    fn next(&mut self) -> Result<Option<ValueRef<'a>>> {
+        let mut val = ptr::null_mut();
+        let rc = unsafe {
+            if self.first {
+                self.first = false;
+                ffi::sqlite3_vtab_in_first(self.list, &mut val)
+            } else {
+                ffi::sqlite3_vtab_in_next(self.list, &mut val)
+            }
+        };
+        match rc {
+            ffi::SQLITE_OK => Ok(Some(unsafe { ValueRef::from_value(val) })),
+            ffi::SQLITE_DONE => Ok(None),
+            _ => Err(error_from_sqlite_code(rc, None)),
+        }
+    }
 }
 
 impl<'a> IntoIterator for &'a Values<'a> {
@@ -674,6 +1024,34 @@ unsafe extern "C" fn free_boxed_value<T>(p: *mut c_void) {
     let _: Box<T> = Box::from_raw(p as *mut T);
 }
 
+/// Allocation of a virtual table instance, along with the state kept on its
+/// behalf. `vtab` comes first, so that a pointer to an `OwnedVTab<T>` is also
+/// a pointer to `T` and to its `sqlite3_vtab`.
+#[repr(C)]
+struct OwnedVTab<T> {
+    vtab: T,
+    // Functions returned by `VTab::find_function`, keyed by function name and
+    // number of arguments: SQLite requires them to stay valid for the life of
+    // the virtual table.
+    #[cfg(feature = "functions")]
+    functions: HashMap<(String, c_int), Box<VTabFunction>>,
+}
+
+impl<T> OwnedVTab<T> {
+    fn into_raw(vtab: T) -> *mut ffi::sqlite3_vtab {
+        let owned = Box::new(OwnedVTab {
+            vtab,
+            #[cfg(feature = "functions")]
+            functions: HashMap::new(),
+        });
+        Box::into_raw(owned) as *mut ffi::sqlite3_vtab
+    }
+
+    unsafe fn drop_raw(vtab: *mut ffi::sqlite3_vtab) {
+        let _: Box<OwnedVTab<T>> = Box::from_raw(vtab as *mut OwnedVTab<T>);
+    }
+}
+
 unsafe extern "C" fn rust_create<T>(
     db: *mut ffi::sqlite3,
     aux: *mut c_void,
@@ -699,8 +1077,7 @@ where
             Ok(c_sql) => {
                 let rc = ffi::sqlite3_declare_vtab(db, c_sql.as_ptr());
                 if rc == ffi::SQLITE_OK {
-                    let boxed_vtab: *mut T = Box::into_raw(Box::new(vtab));
-                    *pp_vtab = boxed_vtab as *mut ffi::sqlite3_vtab;
+                    *pp_vtab = OwnedVTab::into_raw(vtab);
                     ffi::SQLITE_OK
                 } else {
                     let err = error_from_sqlite_code(rc, None);
@@ -751,8 +1128,7 @@ where
             Ok(c_sql) => {
                 let rc = ffi::sqlite3_declare_vtab(db, c_sql.as_ptr());
                 if rc == ffi::SQLITE_OK {
-                    let boxed_vtab: *mut T = Box::into_raw(Box::new(vtab));
-                    *pp_vtab = boxed_vtab as *mut ffi::sqlite3_vtab;
+                    *pp_vtab = OwnedVTab::into_raw(vtab);
                     ffi::SQLITE_OK
                 } else {
                     let err = error_from_sqlite_code(rc, None);
@@ -809,8 +1185,7 @@ where
     if vtab.is_null() {
         return ffi::SQLITE_OK;
     }
-    let vtab = vtab as *mut T;
-    let _: Box<T> = Box::from_raw(vtab);
+    OwnedVTab::<T>::drop_raw(vtab);
     ffi::SQLITE_OK
 }
 
@@ -824,7 +1199,7 @@ where
     let vt = vtab as *mut T;
     match (*vt).destroy() {
         Ok(_) => {
-            let _: Box<T> = Box::from_raw(vt);
+            OwnedVTab::<T>::drop_raw(vtab);
             ffi::SQLITE_OK
         }
         Err(Error::SqliteFailure(err, s)) => {
@@ -946,6 +1321,175 @@ where
     }
 }
 
+#[cfg(feature = "functions")]
+unsafe extern "C" fn rust_find_function<T>(
+    vtab: *mut ffi::sqlite3_vtab,
+    n_arg: c_int,
+    z_name: *const c_char,
+    px_func: *mut Option<
+        unsafe extern "C" fn(*mut ffi::sqlite3_context, c_int, *mut *mut ffi::sqlite3_value),
+    >,
+    pp_arg: *mut *mut c_void,
+) -> c_int
+where
+    T: VTab,
+{
+    use crate::functions::call_boxed_closure;
+    use std::ffi::CStr;
+
+    let name = match CStr::from_ptr(z_name).to_str() {
+        Ok(name) => name,
+        Err(_) => return 0,
+    };
+    let owned = vtab as *mut OwnedVTab<T>;
+    let vt = &(*owned).vtab;
+    let functions = &mut (*owned).functions;
+    let key = (name.to_owned(), n_arg);
+    let boxed_f = match functions.get_mut(&key) {
+        Some(boxed_f) => boxed_f,
+        None => match vt.find_function(n_arg, name) {
+            Some(f) => functions.entry(key).or_insert_with(|| Box::new(f)),
+            None => return 0,
+        },
+    };
+    *px_func = Some(call_boxed_closure::<VTabFunction, ToSqlOutput<'static>>);
+    *pp_arg = &mut **boxed_f as *mut VTabFunction as *mut c_void;
+    #[cfg(feature = "modern_sqlite")]
+    {
+        if let Some(op) = vt.function_constraint(n_arg, name) {
+            if c_int::from(op) >= ffi::SQLITE_INDEX_CONSTRAINT_FUNCTION {
+                return op.into();
+            }
+        }
+    }
+    1
+}
+
+#[cfg(feature = "vtab_v3")]
+unsafe extern "C" fn rust_shadow_name<T>(name: *const c_char) -> c_int
+where
+    T: VTab,
+{
+    use std::ffi::CStr;
+
+    match CStr::from_ptr(name).to_str() {
+        Ok(name) => T::shadow_name(name) as c_int,
+        Err(_) => 0,
+    }
+}
+
+unsafe extern "C" fn rust_rename<T>(vtab: *mut ffi::sqlite3_vtab, z_new: *const c_char) -> c_int
+where
+    T: VTab,
+{
+    use std::ffi::CStr;
+
+    let vt = vtab as *mut T;
+    let r = CStr::from_ptr(z_new)
+        .to_str()
+        .map_err(Error::Utf8Error)
+        .and_then(|new_name| (*vt).rename(new_name));
+    vtab_error(vtab, r)
+}
+
+unsafe extern "C" fn rust_update<T>(
+    vtab: *mut ffi::sqlite3_vtab,
+    argc: c_int,
+    argv: *mut *mut ffi::sqlite3_value,
+    p_rowid: *mut ffi::sqlite3_int64,
+) -> c_int
+where
+    T: UpdateVTab,
+{
+    assert!(argc >= 1);
+    let args = slice::from_raw_parts_mut(argv, argc as usize);
+    let vt = vtab as *mut T;
+    let r = if args.len() == 1 {
+        (*vt).delete(ValueRef::from_value(args[0]))
+    } else if ffi::sqlite3_value_type(args[0]) == ffi::SQLITE_NULL {
+        let values = Values { args };
+        (*vt).insert(&values).map(|rowid| *p_rowid = rowid)
+    } else {
+        let values = Values { args };
+        (*vt).update(&values)
+    };
+    vtab_error(vtab, r)
+}
+
+unsafe extern "C" fn rust_begin<T>(vtab: *mut ffi::sqlite3_vtab) -> c_int
+where
+    T: TransactionVTab,
+{
+    let vt = vtab as *mut T;
+    vtab_error(vtab, (*vt).begin())
+}
+
+unsafe extern "C" fn rust_sync<T>(vtab: *mut ffi::sqlite3_vtab) -> c_int
+where
+    T: TransactionVTab,
+{
+    let vt = vtab as *mut T;
+    vtab_error(vtab, (*vt).sync())
+}
+
+unsafe extern "C" fn rust_commit<T>(vtab: *mut ffi::sqlite3_vtab) -> c_int
+where
+    T: TransactionVTab,
+{
+    let vt = vtab as *mut T;
+    vtab_error(vtab, (*vt).commit())
+}
+
+unsafe extern "C" fn rust_rollback<T>(vtab: *mut ffi::sqlite3_vtab) -> c_int
+where
+    T: TransactionVTab,
+{
+    let vt = vtab as *mut T;
+    vtab_error(vtab, (*vt).rollback())
+}
+
+unsafe extern "C" fn rust_savepoint<T>(vtab: *mut ffi::sqlite3_vtab, n: c_int) -> c_int
+where
+    T: TransactionVTab,
+{
+    let vt = vtab as *mut T;
+    vtab_error(vtab, (*vt).savepoint(n))
+}
+
+unsafe extern "C" fn rust_release<T>(vtab: *mut ffi::sqlite3_vtab, n: c_int) -> c_int
+where
+    T: TransactionVTab,
+{
+    let vt = vtab as *mut T;
+    vtab_error(vtab, (*vt).release(n))
+}
+
+unsafe extern "C" fn rust_rollback_to<T>(vtab: *mut ffi::sqlite3_vtab, n: c_int) -> c_int
+where
+    T: TransactionVTab,
+{
+    let vt = vtab as *mut T;
+    vtab_error(vtab, (*vt).rollback_to(n))
+}
+
+/// Virtual tables methods can set an error message by assigning a string to
+/// `zErrMsg`.
+unsafe fn vtab_error<T>(vtab: *mut ffi::sqlite3_vtab, result: Result<T>) -> c_int {
+    match result {
+        Ok(_) => ffi::SQLITE_OK,
+        Err(Error::SqliteFailure(err, s)) => {
+            if let Some(err_msg) = s {
+                set_err_msg(vtab, &err_msg);
+            }
+            err.extended_code
+        }
+        Err(err) => {
+            set_err_msg(vtab, &err.to_string());
+            ffi::SQLITE_ERROR
+        }
+    }
+}
+
 /// Virtual table cursors can set an error message by assigning a string to
 /// `zErrMsg`.
 unsafe fn cursor_error<T>(cursor: *mut ffi::sqlite3_vtab_cursor, result: Result<T>) -> c_int {
@@ -1059,4 +1603,452 @@ mod test {
         assert_eq!(Some(false), super::parse_boolean("off"));
         assert_eq!(Some(false), super::parse_boolean("false"));
     }
+
+    mod kv {
+        use std::cell::RefCell;
+        use std::collections::BTreeMap;
+        use std::os::raw::c_int;
+        use std::rc::Rc;
+
+        use crate::types::ValueRef;
+        use crate::vtab::IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_EQ;
+        #[cfg(feature = "modern_sqlite")]
+        use crate::vtab::IndexFlags;
+        #[cfg(feature = "vtab_v3")]
+        use crate::vtab::VTabConfig;
+        #[cfg(feature = "functions")]
+        use crate::vtab::VTabFunction;
+        use crate::vtab::{
+            sqlite3_vtab, sqlite3_vtab_cursor, Context, CreateVTab, IndexInfo, TransactionVTab,
+            UpdateVTab, VTab, VTabConnection, VTabCursor, Values,
+        };
+        #[cfg(feature = "vtab_planner")]
+        use crate::vtab::{DistinctMode, IndexConstraintOp::SQLITE_INDEX_CONSTRAINT_LIMIT};
+        use crate::Result;
+
+        #[derive(Default)]
+        pub struct Store {
+            pub rows: BTreeMap<i64, String>,
+            pub log: Vec<&'static str>,
+            #[cfg(feature = "vtab_v3")]
+            pub config: Option<VTabConfig>,
+            #[cfg(feature = "modern_sqlite")]
+            pub col_used: u64,
+            #[cfg(feature = "modern_sqlite")]
+            pub collations: Vec<String>,
+            #[cfg(feature = "vtab_planner")]
+            pub distinct: Option<DistinctMode>,
+            #[cfg(feature = "vtab_planner")]
+            pub limit: Option<i64>,
+        }
+
+        pub type Shared = Rc<RefCell<Store>>;
+
+        #[repr(C)]
+        pub struct KvTab {
+            base: sqlite3_vtab,
+            store: Shared,
+        }
+
+        impl VTab for KvTab {
+            type Aux = Shared;
+            type Cursor = KvTabCursor;
+
+            fn connect(
+                _db: &mut VTabConnection,
+                aux: Option<&Shared>,
+                _args: &[&[u8]],
+            ) -> Result<(String, KvTab)> {
+                #[cfg(feature = "vtab_v3")]
+                {
+                    if let Some(config) = aux.unwrap().borrow().config {
+                        _db.config(config)?;
+                    }
+                }
+                let vtab = KvTab {
+                    base: sqlite3_vtab::default(),
+                    store: aux.unwrap().clone(),
+                };
+                Ok(("CREATE TABLE x(value TEXT)".to_owned(), vtab))
+            }
+
+            fn best_index(&self, info: &mut IndexInfo) -> Result<()> {
+                let mut rowid_eq = None;
+                for (i, constraint) in info.constraints().enumerate() {
+                    if constraint.column() == -1
+                        && constraint.is_usable()
+                        && constraint.operator() == SQLITE_INDEX_CONSTRAINT_EQ
+                    {
+                        rowid_eq = Some(i);
+                    }
+                    #[cfg(feature = "modern_sqlite")]
+                    {
+                        let collation = info.collation(i)?.to_owned();
+                        self.store.borrow_mut().collations.push(collation);
+                    }
+                    #[cfg(feature = "vtab_planner")]
+                    {
+                        if constraint.operator() == SQLITE_INDEX_CONSTRAINT_LIMIT {
+                            let limit = info.rhs_value(i)?.map(|v| v.as_i64()).transpose()?;
+                            self.store.borrow_mut().limit = limit;
+                        }
+                    }
+                }
+                #[cfg(feature = "vtab_planner")]
+                {
+                    self.store.borrow_mut().distinct = Some(info.distinct());
+                }
+                #[cfg(feature = "modern_sqlite")]
+                {
+                    self.store.borrow_mut().col_used = info.col_used();
+                }
+                if let Some(i) = rowid_eq {
+                    let mut usage = info.constraint_usage(i);
+                    usage.set_argv_index(1);
+                    usage.set_omit(true);
+                    #[cfg(feature = "vtab_planner")]
+                    {
+                        if info.set_in_constraint(i, true) {
+                            info.set_idx_str("rowid IN")?;
+                        } else {
+                            info.set_idx_str("rowid =")?;
+                        }
+                    }
+                    #[cfg(not(feature = "vtab_planner"))]
+                    info.set_idx_str("rowid =")?;
+                    #[cfg(feature = "modern_sqlite")]
+                    info.set_idx_flags(IndexFlags::SQLITE_INDEX_SCAN_UNIQUE);
+                    info.set_estimated_cost(1.);
+                } else {
+                    info.set_estimated_cost(1_000.);
+                }
+                Ok(())
+            }
+
+            fn open(&self) -> Result<KvTabCursor> {
+                Ok(KvTabCursor {
+                    base: sqlite3_vtab_cursor::default(),
+                    store: self.store.clone(),
+                    rows: Vec::new(),
+                    i: 0,
+                })
+            }
+
+            #[cfg(feature = "functions")]
+            fn find_function(&self, n_arg: c_int, name: &str) -> Option<VTabFunction> {
+                if n_arg != 2 || !name.eq_ignore_ascii_case("match") {
+                    return None;
+                }
+                Some(Box::new(|ctx| {
+                    // `X MATCH Y` is evaluated as `match(Y, X)`
+                    let pattern = ctx.get::<String>(0)?;
+                    let value = ctx.get::<String>(1)?;
+                    Ok(value.contains(&pattern).into())
+                }))
+            }
+
+            #[cfg(feature = "vtab_v3")]
+            fn shadow_name(name: &str) -> bool {
+                name == "data"
+            }
+
+            fn rename(&mut self, _new_name: &str) -> Result<()> {
+                self.store.borrow_mut().log.push("rename");
+                Ok(())
+            }
+        }
+
+        impl CreateVTab for KvTab {}
+
+        impl UpdateVTab for KvTab {
+            fn delete(&mut self, arg: ValueRef<'_>) -> Result<()> {
+                self.store.borrow_mut().rows.remove(&arg.as_i64()?);
+                Ok(())
+            }
+
+            fn insert(&mut self, args: &Values<'_>) -> Result<i64> {
+                let mut store = self.store.borrow_mut();
+                let rowid = match args.get::<Option<i64>>(1)? {
+                    Some(rowid) => rowid,
+                    None => store.rows.keys().next_back().map_or(1, |k| k + 1),
+                };
+                store.rows.insert(rowid, args.get(2)?);
+                Ok(rowid)
+            }
+
+            fn update(&mut self, args: &Values<'_>) -> Result<()> {
+                let mut store = self.store.borrow_mut();
+                store.rows.remove(&args.get(0)?);
+                store.rows.insert(args.get(1)?, args.get(2)?);
+                Ok(())
+            }
+        }
+
+        impl TransactionVTab for KvTab {
+            fn begin(&mut self) -> Result<()> {
+                self.store.borrow_mut().log.push("begin");
+                Ok(())
+            }
+
+            fn commit(&mut self) -> Result<()> {
+                self.store.borrow_mut().log.push("commit");
+                Ok(())
+            }
+
+            fn rollback(&mut self) -> Result<()> {
+                self.store.borrow_mut().log.push("rollback");
+                Ok(())
+            }
+        }
+
+        #[repr(C)]
+        pub struct KvTabCursor {
+            base: sqlite3_vtab_cursor,
+            store: Shared,
+            rows: Vec<(i64, String)>,
+            i: usize,
+        }
+
+        impl VTabCursor for KvTabCursor {
+            fn filter(&mut self, _: c_int, idx_str: Option<&str>, args: &Values<'_>) -> Result<()> {
+                let mut rowids = None;
+                if idx_str == Some("rowid =") {
+                    rowids = Some(vec![args.get::<i64>(0)?]);
+                }
+                #[cfg(feature = "vtab_planner")]
+                {
+                    use fallible_iterator::FallibleIterator;
+                    if idx_str == Some("rowid IN") {
+                        let values = args.in_values(0).map(|v| Ok(v.as_i64()?));
+                        rowids = Some(values.collect()?);
+                    }
+                }
+                self.rows = self
+                    .store
+                    .borrow()
+                    .rows
+                    .iter()
+                    .filter(|(k, _)| match rowids {
+                        Some(ref rowids) => rowids.contains(k),
+                        None => true,
+                    })
+                    .map(|(k, v)| (*k, v.clone()))
+                    .collect();
+                self.i = 0;
+                Ok(())
+            }
+
+            fn next(&mut self) -> Result<()> {
+                self.i += 1;
+                Ok(())
+            }
+
+            fn eof(&self) -> bool {
+                self.i >= self.rows.len()
+            }
+
+            fn column(&self, ctx: &mut Context, _: c_int) -> Result<()> {
+                ctx.set_result(&self.rows[self.i].1)
+            }
+
+            fn rowid(&self) -> Result<i64> {
+                Ok(self.rows[self.i].0)
+            }
+        }
+    }
+
+    #[test]
+    fn test_update_module() {
+        use super::update_module;
+        use crate::{Connection, NO_PARAMS};
+        use kv::{KvTab, Shared};
+
+        let store = Shared::default();
+        let module = update_module::<KvTab>(1).with_transactions();
+        let db = Connection::open_in_memory().unwrap();
+        db.create_module("kv", &module, Some(store.clone()))
+            .unwrap();
+        db.execute_batch("CREATE VIRTUAL TABLE t USING kv()")
+            .unwrap();
+
+        db.execute_batch(
+            "INSERT INTO t(value) VALUES ('a');
+             INSERT INTO t(rowid, value) VALUES (10, 'b');
+             UPDATE t SET value = 'c' WHERE rowid = 1;
+             UPDATE t SET rowid = 11 WHERE rowid = 10;",
+        )
+        .unwrap();
+        let rows: Vec<(i64, String)> = store
+            .borrow()
+            .rows
+            .iter()
+            .map(|(k, v)| (*k, v.clone()))
+            .collect();
+        assert_eq!(vec![(1, "c".to_owned()), (11, "b".to_owned())], rows);
+
+        db.execute("DELETE FROM t WHERE value = 'c'", NO_PARAMS)
+            .unwrap();
+        let value: String = db
+            .query_row("SELECT value FROM t", NO_PARAMS, |r| r.get(0))
+            .unwrap();
+        assert_eq!("b", value);
+        assert_eq!(
+            Some(&"commit"),
+            store.borrow().log.last(),
+            "{:?}",
+            store.borrow().log
+        );
+
+        store.borrow_mut().log.clear();
+        db.execute_batch("BEGIN; INSERT INTO t(value) VALUES ('d'); ROLLBACK;")
+            .unwrap();
+        assert_eq!(vec!["begin", "rollback"], store.borrow().log);
+    }
+
+    #[test]
+    fn test_best_index() {
+        use super::read_only_module;
+        use crate::{Connection, NO_PARAMS};
+        use kv::{KvTab, Shared};
+
+        let store = Shared::default();
+        store.borrow_mut().rows.insert(1, "foo".to_owned());
+        store.borrow_mut().rows.insert(2, "bar".to_owned());
+        store.borrow_mut().rows.insert(3, "baz".to_owned());
+        let module = read_only_module::<KvTab>(1);
+        let db = Connection::open_in_memory().unwrap();
+        db.create_module("kv", &module, Some(store.clone()))
+            .unwrap();
+        db.execute_batch("CREATE VIRTUAL TABLE t USING kv()")
+            .unwrap();
+
+        let value: String = db
+            .query_row("SELECT value FROM t WHERE rowid = 2", NO_PARAMS, |r| {
+                r.get(0)
+            })
+            .unwrap();
+        assert_eq!("bar", value);
+        let values: Vec<String> = db
+            .prepare("SELECT value FROM t WHERE rowid IN (1, 3) ORDER BY 1")
+            .unwrap()
+            .query_map(NO_PARAMS, |r| r.get(0))
+            .unwrap()
+            .collect::<crate::Result<_>>()
+            .unwrap();
+        assert_eq!(vec!["baz", "foo"], values);
+
+        #[cfg(feature = "modern_sqlite")]
+        {
+            store.borrow_mut().collations.clear();
+            let count: i64 = db
+                .query_row(
+                    "SELECT count(*) FROM t WHERE value = 'FOO' COLLATE NOCASE",
+                    NO_PARAMS,
+                    |r| r.get(0),
+                )
+                .unwrap();
+            assert_eq!(1, count);
+            assert_eq!(vec!["NOCASE"], store.borrow().collations);
+            assert_eq!(1, store.borrow().col_used);
+        }
+        #[cfg(feature = "vtab_planner")]
+        {
+            use super::DistinctMode;
+            db.prepare("SELECT DISTINCT value FROM t").unwrap();
+            assert_eq!(Some(DistinctMode::Distinct), store.borrow().distinct);
+            db.prepare("SELECT value FROM t LIMIT 2").unwrap();
+            assert_eq!(Some(2), store.borrow().limit);
+        }
+    }
+
+    #[test]
+    fn test_rename() {
+        use super::read_only_module;
+        use crate::Connection;
+        use kv::{KvTab, Shared};
+
+        let store = Shared::default();
+        let module = read_only_module::<KvTab>(1);
+        let db = Connection::open_in_memory().unwrap();
+        db.create_module("kv", &module, Some(store.clone()))
+            .unwrap();
+        db.execute_batch("CREATE VIRTUAL TABLE t USING kv(); ALTER TABLE t RENAME TO u;")
+            .unwrap();
+        assert_eq!(vec!["rename"], store.borrow().log);
+    }
+
+    #[test]
+    #[cfg(feature = "vtab_v3")]
+    fn test_shadow_name() {
+        use super::read_only_module;
+        use crate::config::DbConfig;
+        use crate::{Connection, NO_PARAMS};
+        use kv::{KvTab, Shared};
+
+        let module = read_only_module::<KvTab>(3);
+        let db = Connection::open_in_memory().unwrap();
+        db.create_module("kv", &module, Some(Shared::default()))
+            .unwrap();
+        db.execute_batch(
+            "CREATE VIRTUAL TABLE t USING kv();
+             CREATE TABLE t_data(x);
+             CREATE TABLE t_other(x);",
+        )
+        .unwrap();
+        db.set_db_config(DbConfig::SQLITE_DBCONFIG_DEFENSIVE, true)
+            .unwrap();
+        db.execute("INSERT INTO t_data VALUES (1)", NO_PARAMS)
+            .unwrap_err();
+        db.execute("INSERT INTO t_other VALUES (1)", NO_PARAMS)
+            .unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "vtab_v3")]
+    fn test_config() {
+        use super::{read_only_module, VTabConfig};
+        use crate::{Connection, NO_PARAMS};
+        use kv::{KvTab, Shared};
+
+        let store = Shared::default();
+        store.borrow_mut().config = Some(VTabConfig::DirectOnly);
+        let module = read_only_module::<KvTab>(3);
+        let db = Connection::open_in_memory().unwrap();
+        db.create_module("kv", &module, Some(store)).unwrap();
+        db.execute_batch("CREATE VIRTUAL TABLE t USING kv(); CREATE VIEW v AS SELECT * FROM t;")
+            .unwrap();
+        db.query_row("SELECT count(*) FROM t", NO_PARAMS, |r| r.get::<_, i64>(0))
+            .unwrap();
+        db.query_row("SELECT count(*) FROM v", NO_PARAMS, |r| r.get::<_, i64>(0))
+            .unwrap_err();
+    }
+
+    #[test]
+    #[cfg(feature = "functions")]
+    fn test_find_function() {
+        use super::read_only_module;
+        use crate::{Connection, NO_PARAMS};
+        use kv::{KvTab, Shared};
+
+        let store = Shared::default();
+        store.borrow_mut().rows.insert(1, "foo".to_owned());
+        store.borrow_mut().rows.insert(2, "bar".to_owned());
+        let module = read_only_module::<KvTab>(1);
+        let db = Connection::open_in_memory().unwrap();
+        db.create_module("kv", &module, Some(store)).unwrap();
+        db.execute_batch("CREATE VIRTUAL TABLE t USING kv()")
+            .unwrap();
+
+        for _ in 0..2 {
+            let mut stmt = db
+                .prepare("SELECT rowid FROM t WHERE value MATCH 'ar'")
+                .unwrap();
+            let rowids: Vec<i64> = stmt
+                .query_map(NO_PARAMS, |r| r.get(0))
+                .unwrap()
+                .collect::<crate::Result<_>>()
+                .unwrap();
+            assert_eq!(vec![2], rowids);
+        }
+    }
 }
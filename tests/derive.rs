@@ -0,0 +1,98 @@
+//! Ensure `FromRow` and `ToParams` can be derived outside `rusqlite` crate.
+#![cfg(feature = "derive")]
+
+use rusqlite::{Connection, FromRow, Result, ToParams, NO_PARAMS};
+
+#[derive(Debug, Default, PartialEq, FromRow, ToParams)]
+struct Person {
+    id: i64,
+    #[rusqlite(rename = "full_name")]
+    name: String,
+    email: Option<String>,
+    #[rusqlite(default)]
+    nickname: Option<String>,
+    #[rusqlite(skip)]
+    visits: u32,
+}
+
+#[derive(Debug, PartialEq, FromRow, ToParams)]
+struct Pair(i64, String);
+
+#[derive(Debug, PartialEq, FromRow, ToParams)]
+struct Triple(i64, #[rusqlite(skip)] u32, String);
+
+#[derive(Debug, PartialEq, FromRow)]
+struct Second {
+    #[rusqlite(index = 1)]
+    value: String,
+}
+
+fn checked_memory_handle() -> Connection {
+    let db = Connection::open_in_memory().unwrap();
+    db.execute_batch("CREATE TABLE person (id INTEGER PRIMARY KEY, full_name TEXT, email TEXT)")
+        .unwrap();
+    db
+}
+
+#[test]
+fn test_to_params_from_row() -> Result<()> {
+    let db = checked_memory_handle();
+    let alice = Person {
+        id: 1,
+        name: "Alice".to_owned(),
+        email: Some("alice@example.com".to_owned()),
+        nickname: None,
+        visits: 42,
+    };
+    let bob = Person {
+        id: 2,
+        name: "Bob".to_owned(),
+        ..Person::default()
+    };
+    let mut stmt =
+        db.prepare("INSERT INTO person (id, full_name, email) VALUES (:id, @full_name, $email)")?;
+    assert_eq!(1, stmt.execute_params(&alice)?);
+    assert_eq!(1, stmt.execute_params(&bob)?);
+
+    let mut stmt = db.prepare("SELECT id, full_name, email FROM person ORDER BY id")?;
+    let people = stmt
+        .query_as::<Person, _>(NO_PARAMS)?
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(vec![Person { visits: 0, ..alice }, bob], people);
+    Ok(())
+}
+
+#[test]
+fn test_tuple_struct() -> Result<()> {
+    let db = checked_memory_handle();
+    let mut stmt = db.prepare("INSERT INTO person (id, full_name) VALUES (?, ?)")?;
+    stmt.execute_params(&Pair(3, "Carol".to_owned()))?;
+
+    let pair: Pair = db.query_row_as("SELECT id, full_name FROM person", NO_PARAMS)?;
+    assert_eq!(Pair(3, "Carol".to_owned()), pair);
+    let second: Second = db.query_row_as("SELECT id, full_name FROM person", NO_PARAMS)?;
+    assert_eq!("Carol", second.value);
+    Ok(())
+}
+
+#[test]
+fn test_tuple_struct_skip() -> Result<()> {
+    let db = checked_memory_handle();
+    let mut stmt = db.prepare("INSERT INTO person (id, full_name) VALUES (?, ?)")?;
+    stmt.execute_params(&Triple(4, 7, "Erin".to_owned()))?;
+
+    let triple: Triple = db.query_row_as("SELECT id, full_name FROM person", NO_PARAMS)?;
+    assert_eq!(Triple(4, 0, "Erin".to_owned()), triple);
+    Ok(())
+}
+
+#[test]
+fn test_missing_column() {
+    let db = checked_memory_handle();
+    db.execute_batch("INSERT INTO person (id, full_name) VALUES (1, 'Dave')")
+        .unwrap();
+    let err = db
+        .query_row_as::<Person, _>("SELECT id, full_name FROM person", NO_PARAMS)
+        .unwrap_err();
+    assert_eq!(rusqlite::Error::InvalidColumnName("email".to_owned()), err);
+}